@@ -1,5 +1,24 @@
+use clap::Parser;
 use std::error::Error;
+use std::time::Duration;
+
+mod watch;
 
 pub fn main() -> Result<(), Box<dyn Error>> {
-    ara::launch()
-}
\ No newline at end of file
+    let args = ara::Args::parse();
+    if let Some(ara::Command::Watch { debounce }) = args.command {
+        return watch::watch(args, Duration::from_millis(debounce));
+    }
+
+    let options = ara::Options::from_args(args)?;
+    let analysis = ara::analyze(options.clone())?;
+
+    ara::output::write(&analysis, &options)?;
+    for diagnostic in &analysis.diagnostics {
+        eprintln!("Warning: {}", diagnostic);
+    }
+    if let Some(summary) = analysis.unsupported_summary() {
+        eprintln!("{}", summary);
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+/*-------------
+/watch.rs
+
+This file is for the watch subcommand. The project is watched for changes, and once the changes settle down the project
+is analyzed again. Unchanged files come out of the cache so only the changed files are visited again, then the outputs
+are written again and a summary of the API changes since the last run is printed.
+-------------*/
+use std::error::Error;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use ara::{Analysis, Args, Changes, Options};
+use ara::cache::CACHE_DIR;
+use ara::file::Filter;
+use ara::input::source::DiskSource;
+
+/// Watch the project of the arguments and analyze it again whenever a file changes
+pub fn watch(args: Args, debounce: Duration) -> Result<(), Box<dyn Error>> {
+    let mut options = Options::from_args(args.clone())?;
+    let root = Path::new(&options.path).canonicalize()?;
+    let mut previous = run(&options)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+    eprintln!("Watching {} for changes", root.display());
+
+    loop {
+        // Errors in the options are printed and the changes are waited for anyway so they can be fixed while watching
+        let filter = match Filter::new(&options) {
+            Ok(filter) => Some(filter),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                None
+            },
+        };
+        let changed = wait_for_changes(&receiver, debounce, &root, filter.as_ref(), &options)?;
+        eprintln!("Changed: {}", changed.join(", "));
+
+        // The options are loaded every time so changes to ara.toml are picked up as well
+        options = match Options::from_args(args.clone()) {
+            Ok(options) => options,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                continue;
+            },
+        };
+        match run(&options) {
+            Ok(analysis) => {
+                eprintln!("{}", Changes::between(&previous, &analysis));
+                previous = analysis;
+            },
+            Err(err) => eprintln!("Error: {}", err),
+        }
+    }
+}
+
+/// Analyze the project and write the outputs
+fn run(options: &Options) -> Result<Analysis, Box<dyn Error>> {
+    let analysis = ara::analyze(options.clone())?;
+    ara::output::write(&analysis, options)?;
+    for diagnostic in &analysis.diagnostics {
+        eprintln!("Warning: {}", diagnostic);
+    }
+    if let Some(summary) = analysis.unsupported_summary() {
+        eprintln!("{}", summary);
+    }
+    Ok(analysis)
+}
+
+/// Wait for changes to paths that aren't ignored, then keep collecting changes until none came in for the debounce time
+/// Every path is relevant when there is no filter, the only error is the watcher going away.
+fn wait_for_changes(receiver: &Receiver<notify::Result<Event>>, debounce: Duration, root: &Path, filter: Option<&Filter>, options: &Options) -> Result<Vec<String>, Box<dyn Error>> {
+    let source = DiskSource::new(&options.path);
+    let mut changed = Vec::new();
+
+    loop {
+        let event = if changed.is_empty() {
+            receiver.recv()?
+        } else {
+            match receiver.recv_timeout(debounce) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => return Ok(changed),
+                Err(RecvTimeoutError::Disconnected) => return Err(RecvTimeoutError::Disconnected.into()),
+            }
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                eprintln!("Warning: {}", err);
+                continue;
+            },
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+
+        for path in event.paths {
+            if let Some(path) = relative_path(root, &path) {
+                if is_relevant(&path, filter, &source, options) && !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+    }
+}
+
+/// Get the path relative to the project joined with "/"
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let components: Vec<String> = relative.components().map(|component| component.as_os_str().to_string_lossy().to_string()).collect();
+    if components.is_empty() {
+        return None;
+    }
+    Some(components.join("/"))
+}
+
+/// Check if a change to the path should run the analysis again
+/// The cache and the outputs are skipped even when they aren't ignored, writing them would run the analysis forever.
+fn is_relevant(path: &str, filter: Option<&Filter>, source: &DiskSource, options: &Options) -> bool {
+    if path.starts_with(CACHE_DIR) || options.outputs.iter().any(|output| output.path.as_deref().map(|output| output.trim_start_matches("./")) == Some(path)) {
+        return false;
+    }
+    let is_dir = Path::new(&options.path).join(path).is_dir();
+    !filter.is_some_and(|filter| filter.is_path_ignored(source, path, is_dir))
+}
@@ -0,0 +1,206 @@
+/*-------------
+/analysis.rs
+
+This file holds the result of an analysis run. Everything the visitor collects ends up here so it can be returned to
+the caller instead of being printed.
+-------------*/
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::index::Lookup;
+use crate::input::toml::Package;
+use crate::types::TypeRegistry;
+
+/// The result of analyzing a project
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Analysis {
+	/// Every function that was visited with its own graph
+	pub functions: Vec<Function>,
+	/// The structs, enums, unions and type aliases of the workspace by their fully qualified path
+	pub types: TypeRegistry,
+	/// The members of the workspace
+	pub members: Vec<String>,
+	/// The packages of the workspace with their targets
+	pub packages: Vec<Package>,
+	/// The files that were parsed
+	pub parsed: Vec<String>,
+	/// The files that were skipped because they were ignored or couldn't be parsed
+	pub skipped: Vec<String>,
+	/// The files whose results were loaded from the cache instead of being visited
+	pub cached: Vec<String>,
+	/// Errors for files that couldn't be analyzed, these don't stop the rest of the analysis
+	pub diagnostics: Vec<Error>,
+	/// The constructs that were encountered but not interpreted, a function whose graph is missing calls has them
+	pub unsupported: Vec<Unsupported>,
+	/// The calls that are linked to the functions of the workspace they call, across files and crates
+	pub links: Vec<Link>,
+}
+
+impl Analysis {
+	/// Get a function by its path with the name of its crate Eg. "app::routes::create_routes"
+	/// The keys of the functions start with `crate` so they're the same in every crate of the workspace.
+	pub fn get_function(&self, path: &str) -> Option<&Function> {
+		self.functions.iter().find(|function| function.path() == path)
+	}
+
+	/// Get the paths of the functions that a function calls Eg. `["app::routes::create_routes"]` for "app::main"
+	pub fn callees(&self, path: &str) -> Vec<&str> {
+		self.links.iter().filter(|link| link.caller == path).map(|link| link.callee.as_str()).collect()
+	}
+
+	/// Get the paths of the functions that call a function
+	pub fn callers(&self, path: &str) -> Vec<&str> {
+		self.links.iter().filter(|link| link.callee == path).map(|link| link.caller.as_str()).collect()
+	}
+
+	/// Get a summary that counts every unsupported construct with the places it was found, None when there are none
+	pub fn unsupported_summary(&self) -> Option<String> {
+		if self.unsupported.is_empty() {
+			return None;
+		}
+
+		let mut constructs: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+		for unsupported in &self.unsupported {
+			constructs.entry(&unsupported.construct).or_default().push(format!("{}:{}", unsupported.file, unsupported.line));
+		}
+		let lines: Vec<String> = constructs.iter().map(|(construct, places)| format!("  {} x{} ({})", construct, places.len(), places.join(", "))).collect();
+		Some(format!("{} unsupported constructs were not interpreted:\n{}", self.unsupported.len(), lines.join("\n")))
+	}
+}
+
+/// A construct that the visitor encountered but didn't interpret Eg. a macro it can't look into
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Unsupported {
+	/// What the construct is Eg. "macro `sqlx::query!`"
+	pub construct: String,
+	pub file: String,
+	pub line: usize,
+}
+
+/// A call in the graph of a function that is linked to the function it calls
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Link {
+	/// The path of the function that makes the call Eg. "app::main"
+	pub caller: String,
+	/// The id of the node of the call in the graph of the caller
+	pub node: i16,
+	/// The path of the function that is called Eg. "app::routes::create_routes"
+	pub callee: String,
+}
+
+/// A function definition and the graph of the calls it makes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Function {
+	/// The path of the function in its crate Eg. "crate::routes::create_routes", "crate::users::UserService::find" or
+	/// "<crate::users::PgRepo as UserRepo>::find"
+	pub key: String,
+	/// The crate the function is defined in
+	pub krate: String,
+	/// The canonical path of the module the function is defined in, the paths of its calls are resolved in it
+	/// Eg. "app::routes"
+	#[serde(default)]
+	pub module: String,
+	/// The file the function is defined in
+	pub file: String,
+	/// The cfg conditions the function is gated behind Eg. `feature = "postgres"`
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub cfg: Vec<String>,
+	/// The type of the impl block the method is in Eg. "crate::users::UserService"
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub self_type: Option<String>,
+	/// The trait that the impl block implements Eg. "UserRepo"
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub trait_name: Option<String>,
+	/// The self receiver of the method Eg. "&mut self"
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub receiver: Option<String>,
+	/// The graph of the calls made in the function
+	pub graph: Graph,
+}
+
+impl Function {
+	/// Get the path of the function with the name of its crate instead of `crate` Eg. "app::routes::create_routes"
+	pub fn path(&self) -> String {
+		crate_path(&self.key, &self.krate)
+	}
+}
+
+/// Get the path of a key of a function with the name of the crate instead of `crate`
+/// Eg. "<crate::repo::PgRepo as UserRepo>::find" of "app" is "<app::repo::PgRepo as UserRepo>::find".
+pub fn crate_path(key: &str, krate: &str) -> String {
+	if let Some(rest) = key.strip_prefix("crate") {
+		return format!("{}{}", krate, rest);
+	}
+	match key.strip_prefix("<crate") {
+		Some(rest) => format!("<{}{}", krate, rest),
+		None => key.to_string(),
+	}
+}
+
+/// The result of analyzing a single file, the results of every file are merged into the `Analysis`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileAnalysis {
+	/// Path of the file relative to the project
+	pub path: String,
+	/// The functions that are defined in the file
+	pub functions: Vec<Function>,
+	/// Errors that happened while analyzing the file
+	pub diagnostics: Vec<Error>,
+	/// The constructs of the file that weren't interpreted
+	pub unsupported: Vec<Unsupported>,
+	/// The lookups of the file in the index with their answers, the result is only valid while the answers are the same
+	pub lookups: Vec<(Lookup, String)>,
+}
+
+/// The functions that changed between two analysis runs
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+	/// Functions that only exist in the new analysis
+	pub added: Vec<String>,
+	/// Functions that only exist in the old analysis
+	pub removed: Vec<String>,
+	/// Functions whose graph or file changed
+	pub changed: Vec<String>,
+}
+
+impl Changes {
+	/// Compare the functions of two analysis runs by their path
+	pub fn between(old: &Analysis, new: &Analysis) -> Changes {
+		let mut changes = Changes::default();
+		for function in &new.functions {
+			let path = function.path();
+			match old.functions.iter().find(|old| old.path() == path) {
+				None => changes.added.push(path),
+				Some(old) if old.file != function.file || old.cfg != function.cfg || old.graph.nodes != function.graph.nodes || old.graph.edges != function.graph.edges => changes.changed.push(path),
+				Some(_) => {},
+			}
+		}
+		for function in &old.functions {
+			let path = function.path();
+			if !new.functions.iter().any(|new| new.path() == path) {
+				changes.removed.push(path);
+			}
+		}
+		changes
+	}
+
+	/// Check if nothing changed
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+impl Display for Changes {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if self.is_empty() {
+			return write!(f, "No API changes");
+		}
+		let mut lines = Vec::new();
+		lines.extend(self.added.iter().map(|path| format!("+ {}", path)));
+		lines.extend(self.removed.iter().map(|path| format!("- {}", path)));
+		lines.extend(self.changed.iter().map(|path| format!("~ {}", path)));
+		write!(f, "{} added, {} removed, {} changed\n{}", self.added.len(), self.removed.len(), self.changed.len(), lines.join("\n"))
+	}
+}
@@ -0,0 +1,126 @@
+/*-------------
+/cache.rs
+
+This file is for the incremental analysis cache. The result of every file is stored in the cache directory under a
+hash of the contents of the file, the version of ara and everything else the result depends on, so files that didn't
+change are loaded instead of being parsed and visited again. Entries that weren't used by a run are pruned after it so
+the cache doesn't grow with every change.
+-------------*/
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use crate::cfg::Cfg;
+use crate::config::Options;
+use crate::log::Log;
+use crate::module::ModuleFile;
+
+/// The directory of the cache relative to the project
+pub const CACHE_DIR: &str = "target/ara";
+
+#[derive(Debug)]
+pub struct Cache {
+	dir: PathBuf,
+	log: Log,
+	/// The keys that were loaded or stored during this run
+	touched: Mutex<BTreeSet<String>>,
+}
+
+impl Cache {
+	/// Create the cache in the directory
+	pub fn new(dir: &str, log: Log) -> Cache {
+		Cache {dir: PathBuf::from(dir), log, touched: Mutex::new(BTreeSet::new())}
+	}
+
+	/// Get the cache of the project at the path of the options, None is returned when caching is turned off
+	pub fn from_options(options: &Options) -> Option<Cache> {
+		if !options.cache {
+			return None;
+		}
+		Some(Cache::new(&format!("{}/{}", options.path, CACHE_DIR), Log::new(options)))
+	}
+
+	/// Get the key of a file, the key changes with anything that changes the result of the file besides the other files
+	/// The other files only change the result through the answers to its lookups in the index.
+	pub fn key(content: &str, file: &ModuleFile, options: &Options, cfg: &Cfg) -> String {
+		let mut hasher = blake3::Hasher::new();
+		for part in [env!("CARGO_PKG_VERSION"), &file.path, &file.krate, &file.dir, &file.module.join("::"), &file.cfg.join(" "), &options.function_name.join(" "), &cfg.key()] {
+			hasher.update(part.as_bytes());
+			hasher.update(&[0]);
+		}
+		hasher.update(content.as_bytes());
+		hasher.finalize().to_hex().to_string()
+	}
+
+	/// Get a key for something else that depends on the same file Eg. its index
+	pub fn derive(key: &str, part: &str) -> String {
+		let mut hasher = blake3::Hasher::new();
+		hasher.update(key.as_bytes());
+		hasher.update(&[0]);
+		hasher.update(part.as_bytes());
+		hasher.finalize().to_hex().to_string()
+	}
+
+	/// Load an entry, None is returned when it isn't cached
+	pub fn load<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+		let contents = fs::read_to_string(self.path(key)).ok()?;
+		match serde_json::from_str(&contents) {
+			Ok(result) => {
+				self.touch(key);
+				Some(result)
+			},
+			Err(err) => {
+				self.log.warn(&format!("Ignoring broken cache entry {}: {}", key, err));
+				None
+			},
+		}
+	}
+
+	/// Store an entry, failing to store it only means the file is visited again next time
+	pub fn store<T: Serialize>(&self, key: &str, result: &T) {
+		self.touch(key);
+		let contents = match serde_json::to_string(result) {
+			Ok(contents) => contents,
+			Err(err) => return self.log.warn(&format!("Failed to serialize cache entry {}: {}", key, err)),
+		};
+
+		// Write to a temporary file first so a parallel or interrupted run never sees half an entry
+		let temporary = self.dir.join(format!("{}.tmp", key));
+		let stored = fs::create_dir_all(&self.dir)
+			.and_then(|_| fs::write(&temporary, contents))
+			.and_then(|_| fs::rename(&temporary, self.path(key)));
+		if let Err(err) = stored {
+			self.log.warn(&format!("Failed to store cache entry {}: {}", key, err));
+		}
+	}
+
+	/// Remove the entries that weren't loaded or stored during this run, they belong to files that changed or are gone
+	pub fn prune(&self) {
+		let Ok(entries) = fs::read_dir(&self.dir) else {
+			return;
+		};
+		let touched = self.touched.lock().unwrap_or_else(|err| err.into_inner());
+		for entry in entries.flatten() {
+			let path = entry.path();
+			let stale = match path.file_stem().and_then(|stem| stem.to_str()) {
+				Some(key) => path.extension().is_some_and(|extension| extension == "json") && !touched.contains(key),
+				None => false,
+			};
+			if stale {
+				if let Err(err) = fs::remove_file(&path) {
+					self.log.warn(&format!("Failed to prune cache entry {}: {}", path.display(), err));
+				}
+			}
+		}
+	}
+
+	fn touch(&self, key: &str) {
+		self.touched.lock().unwrap_or_else(|err| err.into_inner()).insert(key.to_string());
+	}
+
+	fn path(&self, key: &str) -> PathBuf {
+		self.dir.join(format!("{}.json", key))
+	}
+}
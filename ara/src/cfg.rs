@@ -0,0 +1,285 @@
+/*-------------
+/cfg.rs
+
+This file is for evaluating `#[cfg(...)]` attributes. Every crate gets the set of cfg options it would be compiled with,
+the target of the machine ara runs on, the `--cfg` options and the features that are enabled in its manifest. Items
+whose predicate doesn't hold are left out of the analysis, the ones that do hold keep their condition as an annotation.
+-------------*/
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{Attribute, Meta, Token};
+use crate::config::Options;
+use crate::error::{Error, Span};
+use crate::input::toml::{Dependency, Package};
+
+/// A cfg predicate Eg. `all(unix, feature = "postgres")`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+	/// A name on its own Eg. `test`
+	Name(String),
+	/// A key with a value Eg. `feature = "postgres"`
+	KeyValue(String, String),
+	All(Vec<Predicate>),
+	Any(Vec<Predicate>),
+	Not(Box<Predicate>),
+}
+
+impl Predicate {
+	/// Parse the predicate, None is returned when it isn't a valid predicate
+	pub fn parse(meta: &Meta) -> Option<Predicate> {
+		match meta {
+			Meta::Path(path) => Some(Predicate::Name(path.get_ident()?.to_string())),
+			Meta::NameValue(name_value) => match &name_value.value {
+				syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(value), .. }) => Some(Predicate::KeyValue(name_value.path.get_ident()?.to_string(), value.value())),
+				_ => None,
+			},
+			Meta::List(list) => {
+				let nested = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated).ok()?;
+				let mut predicates = nested.iter().map(Predicate::parse).collect::<Option<Vec<_>>>()?;
+				match list.path.get_ident()?.to_string().as_str() {
+					"all" => Some(Predicate::All(predicates)),
+					"any" => Some(Predicate::Any(predicates)),
+					"not" if predicates.len() == 1 => Some(Predicate::Not(Box::new(predicates.remove(0)))),
+					_ => None,
+				}
+			},
+		}
+	}
+
+	/// Parse a cfg option of the command line Eg. `test` or `target_os="windows"`
+	pub fn parse_option(option: &str) -> Option<Predicate> {
+		match Predicate::parse(&syn::parse_str(option).ok()?)? {
+			predicate @ (Predicate::Name(_) | Predicate::KeyValue(_, _)) => Some(predicate),
+			_ => None,
+		}
+	}
+}
+
+impl Display for Predicate {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let join = |predicates: &[Predicate]| predicates.iter().map(|predicate| predicate.to_string()).collect::<Vec<_>>().join(", ");
+		match self {
+			Predicate::Name(name) => write!(f, "{}", name),
+			Predicate::KeyValue(key, value) => write!(f, "{} = {:?}", key, value),
+			Predicate::All(predicates) => write!(f, "all({})", join(predicates)),
+			Predicate::Any(predicates) => write!(f, "any({})", join(predicates)),
+			Predicate::Not(predicate) => write!(f, "not({})", predicate),
+		}
+	}
+}
+
+/// The cfg options a crate is compiled with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cfg {
+	pub names: BTreeSet<String>,
+	pub values: BTreeSet<(String, String)>,
+}
+
+impl Cfg {
+	/// Create the cfg options of the machine ara runs on with the `--cfg` options of the options on top
+	/// Options with a value replace the values of the machine for that key Eg. `target_os="windows"`.
+	pub fn new(options: &Options) -> Cfg {
+		let mut cfg = Cfg::default();
+		cfg.names.insert(std::env::consts::FAMILY.to_string());
+		if options.debug_assertions {
+			cfg.names.insert("debug_assertions".to_string());
+		}
+		for (key, value) in [
+			("target_os", std::env::consts::OS.to_string()),
+			("target_family", std::env::consts::FAMILY.to_string()),
+			("target_arch", std::env::consts::ARCH.to_string()),
+			("target_pointer_width", usize::BITS.to_string()),
+			("target_endian", if cfg!(target_endian = "little") { "little" } else { "big" }.to_string()),
+		] {
+			cfg.values.insert((key.to_string(), value));
+		}
+
+		let mut replaced = BTreeSet::new();
+		for option in &options.cfg {
+			match Predicate::parse_option(option) {
+				Some(Predicate::Name(name)) => { cfg.names.insert(name); },
+				Some(Predicate::KeyValue(key, value)) => {
+					if replaced.insert(key.clone()) {
+						cfg.values.retain(|(existing, _)| *existing != key);
+					}
+					cfg.values.insert((key, value));
+				},
+				_ => {},
+			}
+		}
+		cfg
+	}
+
+	/// Get the cfg options with the features of a crate
+	pub fn with_features(&self, features: &BTreeSet<String>) -> Cfg {
+		let mut cfg = self.clone();
+		cfg.values.extend(features.iter().map(|feature| ("feature".to_string(), feature.clone())));
+		cfg
+	}
+
+	/// Check if the predicate holds
+	pub fn eval(&self, predicate: &Predicate) -> bool {
+		match predicate {
+			Predicate::Name(name) => self.names.contains(name),
+			Predicate::KeyValue(key, value) => self.values.contains(&(key.clone(), value.clone())),
+			Predicate::All(predicates) => predicates.iter().all(|predicate| self.eval(predicate)),
+			Predicate::Any(predicates) => predicates.iter().any(|predicate| self.eval(predicate)),
+			Predicate::Not(predicate) => !self.eval(predicate),
+		}
+	}
+
+	/// Check if every cfg attribute holds, attributes that can't be parsed are treated as holding
+	pub fn is_enabled(&self, attrs: &[Attribute]) -> bool {
+		cfg_attributes(attrs).all(|attr| predicate(attr).is_none_or(|predicate| self.eval(&predicate)))
+	}
+
+	/// Get a key that changes with the cfg options for caching
+	pub fn key(&self) -> String {
+		format!("{:?}", self)
+	}
+}
+
+/// Get the conditions of the cfg attributes Eg. `feature = "postgres"`
+pub fn conditions(attrs: &[Attribute]) -> Vec<String> {
+	cfg_attributes(attrs).map(|attr| match predicate(attr) {
+		Some(predicate) => predicate.to_string(),
+		None => attr.meta.to_token_stream().to_string(),
+	}).collect()
+}
+
+/// Get the errors of the `--cfg` options that aren't a name or a key with a value, they're left out of the cfg options
+pub fn check_options(options: &Options) -> Vec<Error> {
+	options.cfg.iter()
+		.filter(|option| Predicate::parse_option(option).is_none())
+		.map(|option| Error::InvalidArgument {argument: "--cfg".to_string(), message: format!("`{}` isn't a valid cfg option", option)})
+		.collect()
+}
+
+/// Get the errors of the cfg attributes of a file that can't be parsed, anywhere in the file
+/// They're reported here once since every pass treats them as holding.
+pub fn check_attributes(path: &str, syntax: &syn::File) -> Vec<Error> {
+	let mut checker = AttributeChecker {path, errors: Vec::new()};
+	checker.visit_file(syntax);
+	checker.errors
+}
+
+struct AttributeChecker<'a> {
+	path: &'a str,
+	errors: Vec<Error>,
+}
+
+impl<'ast> Visit<'ast> for AttributeChecker<'_> {
+	fn visit_attribute(&mut self, attr: &'ast Attribute) {
+		if attr.path().is_ident("cfg") && predicate(attr).is_none() {
+			let start = attr.span().start();
+			self.errors.push(Error::InvalidCfg {
+				path: self.path.to_string(),
+				predicate: attr.meta.to_token_stream().to_string(),
+				span: Span::new(start.line, start.column + 1),
+			});
+		}
+		visit::visit_attribute(self, attr);
+	}
+}
+
+fn cfg_attributes(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+	attrs.iter().filter(|attr| attr.path().is_ident("cfg"))
+}
+
+fn predicate(attr: &Attribute) -> Option<Predicate> {
+	Predicate::parse(&attr.parse_args().ok()?)
+}
+
+/// Get the enabled features of every package by its name
+/// Packages get the features of the options and their default feature, then the features that are enabled by other
+/// features and by the dependencies between the packages of the workspace are added until nothing changes.
+pub fn features(packages: &[Package], options: &Options) -> BTreeMap<String, BTreeSet<String>> {
+	let mut enabled: BTreeMap<String, BTreeSet<String>> = packages.iter().map(|package| (package.name.clone(), BTreeSet::new())).collect();
+	let mut queue: Vec<(String, String)> = Vec::new();
+
+	for package in packages {
+		if options.all_features {
+			queue.extend(package.features.keys().map(|feature| (package.name.clone(), feature.clone())));
+		}
+		if !options.no_default_features {
+			queue.push((package.name.clone(), "default".to_string()));
+		}
+		for feature in &options.features {
+			match feature.split_once('/') {
+				Some((name, feature)) if name == package.name => queue.push((package.name.clone(), feature.to_string())),
+				Some(_) => {},
+				None => queue.push((package.name.clone(), feature.clone())),
+			}
+		}
+		for dependency in package.dependencies.iter().filter(|dependency| !dependency.optional) {
+			queue.extend(dependency_features(dependency));
+		}
+	}
+
+	while let Some((name, feature)) = queue.pop() {
+		let package = match packages.iter().find(|package| package.name == name) {
+			Some(package) => package,
+			None => continue,
+		};
+		let values = match package.features.get(&feature) {
+			Some(values) => values,
+			None => continue,
+		};
+		if !enabled.entry(name.clone()).or_default().insert(feature) {
+			continue;
+		}
+
+		for value in values {
+			if let Some(dependency) = value.strip_prefix("dep:") {
+				if let Some(dependency) = package.dependencies.iter().find(|found| found.name == dependency) {
+					queue.extend(dependency_features(dependency));
+				}
+				continue;
+			}
+
+			let (dependency, dependency_feature) = match value.split_once('/') {
+				Some(split) => split,
+				None => {
+					queue.push((name.clone(), value.clone()));
+					continue;
+				},
+			};
+			// `dependency?/feature` doesn't enable the dependency itself
+			let weak = dependency.ends_with('?');
+			let dependency = dependency.trim_end_matches('?');
+			if !weak {
+				queue.push((name.clone(), dependency.to_string()));
+			}
+			if let Some(dependency) = package.dependencies.iter().find(|found| found.name == dependency) {
+				if !weak {
+					queue.extend(dependency_features(dependency));
+				}
+				queue.push((dependency.package.clone(), dependency_feature.to_string()));
+			}
+		}
+	}
+	enabled
+}
+
+/// Get the cfg options of every crate of the workspace by the name of the crate
+pub fn crate_cfgs(packages: &[Package], options: &Options) -> BTreeMap<String, Cfg> {
+	let cfg = Cfg::new(options);
+	let features = features(packages, options);
+	packages.iter()
+		.flat_map(|package| package.targets.iter().map(move |target| (target, package)))
+		.map(|(target, package)| (target.crate_name.clone(), cfg.with_features(&features[&package.name])))
+		.collect()
+}
+
+/// The features of a dependency that are enabled by depending on it
+fn dependency_features(dependency: &Dependency) -> Vec<(String, String)> {
+	let mut features: Vec<(String, String)> = dependency.features.iter().map(|feature| (dependency.package.clone(), feature.clone())).collect();
+	if dependency.default_features {
+		features.push((dependency.package.clone(), "default".to_string()));
+	}
+	features
+}
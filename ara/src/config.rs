@@ -1,23 +1,88 @@
 /*-------------
 /config.rs
 
-This file is for the configuration of the application utilizing the clap crate.
+This file is for the configuration of the application utilizing the clap crate. The clap arguments are merged on top of
+the project configuration into `Options` which is what the library actually runs off of, so the library can be embedded
+without parsing argv.
 -------------*/
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_derive::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::input::{project::ProjectConfig, source::DiskSource};
 
-lazy_static! {
-	pub static ref APP: Args = Args::parse();
-}
-
-#[derive(Parser, Debug, Clone)]
+#[derive(Parser, Debug, Clone, Default)]
 pub struct Args {
-	/// Ignore Files Paths
-	#[clap(long, value_delimiter = ' ', num_args = 1.., default_value = "/target /tests")]
-	pub ignore: Vec<String>,
+	/// Ignore Files Paths as gitignore style globs relative to the project Eg. "/target **/generated/** !src/keep.rs" [default: /target /tests]
+	#[clap(long, value_delimiter = ' ', num_args = 1..)]
+	pub ignore: Option<Vec<String>>,
+
+	/// Only analyze the files that match these globs relative to the project Eg. "src/**/*.rs"
+	#[clap(long, value_delimiter = ' ', num_args = 1..)]
+	pub include: Option<Vec<String>>,
+
+	/// Don't honor the .gitignore and .ignore files of the project
+	#[clap(long, default_value = "false")]
+	pub no_gitignore: bool,
+
+	/// Honor the .gitignore and .ignore files even when the project configuration turns them off
+	#[clap(long, default_value = "false", conflicts_with = "no_gitignore")]
+	pub gitignore: bool,
+
+	/// Don't use the analysis cache in target/ara and visit every file again
+	#[clap(long, default_value = "false")]
+	pub no_cache: bool,
+
+	/// Use the analysis cache even when the project configuration turns it off
+	#[clap(long, default_value = "false", conflicts_with = "no_cache")]
+	pub cache: bool,
+
+	/// Features to enable, features of a single package are given as "package/feature" Eg. "postgres api/metrics"
+	#[clap(long, value_delimiter = ' ', num_args = 1..)]
+	pub features: Option<Vec<String>>,
+
+	/// Enable every feature of every package
+	#[clap(long, default_value = "false")]
+	pub all_features: bool,
+
+	/// Don't enable every feature even when the project configuration does
+	#[clap(long, default_value = "false", conflicts_with = "all_features")]
+	pub no_all_features: bool,
+
+	/// Don't enable the default feature of the packages
+	#[clap(long, default_value = "false")]
+	pub no_default_features: bool,
+
+	/// Enable the default features even when the project configuration turns them off
+	#[clap(long, default_value = "false", conflicts_with = "no_default_features")]
+	pub default_features: bool,
+
+	/// Evaluate the cfg attributes like the release profile, `debug_assertions` isn't set
+	#[clap(long, default_value = "false")]
+	pub release: bool,
+
+	/// Evaluate the cfg attributes like the dev profile even when the project configuration sets release
+	#[clap(long, default_value = "false", conflicts_with = "release")]
+	pub dev: bool,
+
+	/// Extra cfg options the same way rustc takes them, can be given more than once Eg. --cfg test --cfg 'target_os="windows"'
+	#[clap(long)]
+	pub cfg: Option<Vec<String>>,
 
 	/// Only runs the program on the specified function names Eg. "main foo bar"
 	#[clap(long, value_delimiter = ' ', num_args = 1..)]
-	pub function_name: Vec<String>,
+	pub function_name: Option<Vec<String>>,
+
+	/// Output formats Eg. "terminal json" [default: terminal]
+	#[clap(long, value_delimiter = ' ', num_args = 1..)]
+	pub format: Option<Vec<Format>>,
+
+	/// Write the output to this file instead of the terminal, only a single format can be written to it
+	#[clap(short, long)]
+	pub output: Option<String>,
+
+	/// Web framework used by the project
+	#[clap(long)]
+	pub framework: Option<Framework>,
 
 	/// Debug mode
 	#[clap(short, long, default_value = "false")]
@@ -27,7 +92,247 @@ pub struct Args {
 	#[clap(short, default_value = "false")]
 	pub verbose: bool,
 
-	/// Path to the project
-	#[clap(short, long, default_value = ".")]
+	/// Path to the project [default: .]
+	#[clap(short, long)]
+	pub path: Option<String>,
+
+	/// Run a subcommand instead of analyzing the project once
+	#[clap(subcommand)]
+	pub command: Option<Command>,
+}
+
+/// Subcommands of the command line
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+	/// Watch the project and analyze it again whenever a file changes
+	Watch {
+		/// Milliseconds to wait for more changes before analyzing again
+		#[clap(long, default_value = "300")]
+		debounce: u64,
+	},
+}
+
+/// Output formats of the analysis
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+	/// Print the graph of every function
+	Terminal,
+	/// The whole analysis as json
+	Json,
+}
+
+/// Web frameworks that the project can be using
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Framework {
+	Axum,
+	Actix,
+	Rocket,
+}
+
+/// An output format and where it is written to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+	pub format: Format,
+	/// The file to write to, the terminal is used when there is no path
+	pub path: Option<String>,
+}
+
+impl Output {
+	pub fn new(format: Format, path: Option<String>) -> Output {
+		Output {format, path}
+	}
+}
+
+/// The settings for a single analysis run
+#[derive(Debug, Clone)]
+pub struct Options {
+	/// Path to the root of the project
 	pub path: String,
-}
\ No newline at end of file
+	/// Gitignore style patterns of the paths to ignore
+	pub ignore: Vec<String>,
+	/// Globs of the files to analyze, every file is analyzed when empty
+	pub include: Vec<String>,
+	/// Honor the .gitignore and .ignore files of the project
+	pub gitignore: bool,
+	/// Load the results of unchanged files from the cache in target/ara, only used when analyzing from the disk
+	/// It's off unless it's turned on, only the command line turns it on by default.
+	pub cache: bool,
+	/// Only analyze functions with these names, analyze everything when empty
+	pub function_name: Vec<String>,
+	/// Features to enable on top of the default features, "package/feature" only enables it for that package
+	pub features: Vec<String>,
+	/// Enable every feature of every package
+	pub all_features: bool,
+	/// Don't enable the default features
+	pub no_default_features: bool,
+	/// Set the `debug_assertions` cfg option like the dev profile does
+	pub debug_assertions: bool,
+	/// Extra cfg options Eg. "test" or "target_os=\"windows\""
+	pub cfg: Vec<String>,
+	/// Where the analysis is written to
+	pub outputs: Vec<Output>,
+	/// Web framework used by the project
+	pub framework: Option<Framework>,
+	/// Print debug information
+	pub debug: bool,
+	/// Print verbose debug information
+	pub verbose: bool,
+}
+
+impl Options {
+	/// Create the options for analyzing the project at the given path
+	pub fn new(path: &str) -> Options {
+		Options {
+			path: path.to_string(),
+			..Options::default()
+		}
+	}
+
+	/// Create the options from the project configuration of the path with the command line arguments on top
+	/// The cache is on by default on the command line, it can still be turned off by the configuration or the arguments.
+	pub fn from_args(args: Args) -> Result<Options> {
+		let path = args.path.clone().unwrap_or_else(|| ".".to_string());
+		let config = ProjectConfig::load(&DiskSource::new(&path))?;
+		let mut options = Options {cache: true, ..Options::new(&path)};
+		options.merge_config(config);
+		options.merge_args(args)?;
+		Ok(options)
+	}
+
+	/// Overwrite the options with everything that is set in the project configuration
+	pub fn merge_config(&mut self, config: ProjectConfig) {
+		if let Some(ignore) = config.ignore {
+			self.ignore = ignore;
+		}
+		if let Some(include) = config.include {
+			self.include = include;
+		}
+		if let Some(gitignore) = config.gitignore {
+			self.gitignore = gitignore;
+		}
+		if let Some(cache) = config.cache {
+			self.cache = cache;
+		}
+		if let Some(function_name) = config.function_name {
+			self.function_name = function_name;
+		}
+		if let Some(features) = config.features {
+			self.features = features;
+		}
+		if let Some(all_features) = config.all_features {
+			self.all_features = all_features;
+		}
+		if let Some(no_default_features) = config.no_default_features {
+			self.no_default_features = no_default_features;
+		}
+		if let Some(release) = config.release {
+			self.debug_assertions = !release;
+		}
+		if let Some(cfg) = config.cfg {
+			self.cfg = cfg;
+		}
+		if let Some(framework) = config.framework {
+			self.framework = Some(framework);
+		}
+
+		let formats = match config.formats {
+			Some(formats) => formats,
+			None if !config.output.is_empty() => config.output.keys().copied().collect(),
+			None => return,
+		};
+		self.outputs = formats.into_iter().map(|format| Output::new(format, config.output.get(&format).cloned())).collect();
+	}
+
+	/// Overwrite the options with everything that is given on the command line
+	/// The output file can only be given for a single format, the formats would overwrite each other in it. The switches
+	/// of the configuration can be turned on and off, debug and verbose can only be turned on since they aren't in it.
+	pub fn merge_args(&mut self, args: Args) -> Result<()> {
+		if let Some(ignore) = args.ignore {
+			self.ignore = ignore;
+		}
+		if let Some(include) = args.include {
+			self.include = include;
+		}
+		set_switch(&mut self.gitignore, args.gitignore, args.no_gitignore);
+		set_switch(&mut self.cache, args.cache, args.no_cache);
+		set_switch(&mut self.all_features, args.all_features, args.no_all_features);
+		set_switch(&mut self.no_default_features, args.no_default_features, args.default_features);
+		set_switch(&mut self.debug_assertions, args.dev, args.release);
+		if let Some(function_name) = args.function_name {
+			self.function_name = function_name;
+		}
+		if let Some(features) = args.features {
+			self.features = features;
+		}
+		if let Some(cfg) = args.cfg {
+			self.cfg = cfg;
+		}
+		if let Some(framework) = args.framework {
+			self.framework = Some(framework);
+		}
+
+		if let Some(formats) = args.format {
+			self.outputs = formats.into_iter().map(|format| {
+				let path = self.get_output(format).and_then(|output| output.path.clone());
+				Output::new(format, path)
+			}).collect();
+		}
+		if let Some(path) = args.output {
+			match self.outputs.as_mut_slice() {
+				[output] => output.path = Some(path),
+				_ => return Err(Error::InvalidArgument {argument: "--output".to_string(), message: "can only be used with a single format".to_string()}),
+			}
+		}
+
+		self.debug |= args.debug;
+		self.verbose |= args.verbose;
+		if let Some(path) = args.path {
+			self.path = path;
+		}
+		Ok(())
+	}
+
+	/// Get the output of a format
+	pub fn get_output(&self, format: Format) -> Option<&Output> {
+		self.outputs.iter().find(|output| output.format == format)
+	}
+
+	/// Check if a function should be analyzed with the current function filter
+	pub fn includes_function(&self, name: &str) -> bool {
+		self.function_name.is_empty() || self.function_name.iter().any(|f| f == name)
+	}
+}
+
+/// Turn an option on or off by the flags that are given, it's kept as it is when neither is given
+fn set_switch(option: &mut bool, on: bool, off: bool) {
+	if on {
+		*option = true;
+	}
+	if off {
+		*option = false;
+	}
+}
+
+impl Default for Options {
+	fn default() -> Options {
+		Options {
+			path: ".".to_string(),
+			ignore: vec!["/target".to_string(), "/tests".to_string()],
+			include: Vec::new(),
+			gitignore: true,
+			cache: false,
+			function_name: Vec::new(),
+			features: Vec::new(),
+			all_features: false,
+			no_default_features: false,
+			debug_assertions: true,
+			cfg: Vec::new(),
+			outputs: vec![Output::new(Format::Terminal, None)],
+			framework: None,
+			debug: false,
+			verbose: false,
+		}
+	}
+}
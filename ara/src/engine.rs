@@ -0,0 +1,228 @@
+/*-------------
+/engine.rs
+
+This file stitches the analysis together. The module tree is discovered and every file is indexed first, then every
+file is visited on its own state across threads with the index of the workspace. The results of the files are merged
+back in the order they were discovered so the output is the same on every run. Files that didn't change since the last
+run are loaded from the cache instead.
+-------------*/
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use syn::visit::Visit;
+use crate::analysis::{Analysis, FileAnalysis, Function, Link};
+use crate::cache::Cache;
+use crate::cfg::{self, Cfg};
+use crate::config::Options;
+use crate::error::{Error, Result};
+use crate::graph::node::NodeType;
+use crate::index::{self, FileIndex, Index};
+use crate::input::source::Source;
+use crate::module::{self, ModuleFile};
+use crate::state::State;
+
+/// Everything the files of a run are analyzed with
+pub struct Context<'a> {
+	pub source: &'a dyn Source,
+	pub options: &'a Options,
+	pub cache: Option<&'a Cache>,
+	/// The cfg options of every crate by the name of the crate
+	pub cfgs: &'a BTreeMap<String, Cfg>,
+}
+
+impl Context<'_> {
+	/// Get the cfg options of a crate
+	pub fn cfg(&self, krate: &str) -> Cfg {
+		self.cfgs.get(krate).cloned().unwrap_or_else(|| Cfg::new(self.options))
+	}
+}
+
+/// A file of the module tree that was read and indexed
+pub struct Discovered {
+	pub file: ModuleFile,
+	pub content: String,
+	/// The cache key of the file on its own, the index it is visited with is added on top
+	pub key: String,
+	pub index: FileIndex,
+}
+
+/// Analyze the module tree from the crate roots
+/// The whole tree is discovered and indexed first so every file can be visited in parallel with the index of the
+/// workspace. Unchanged files are loaded from the cache when there is one.
+pub fn analyze_modules(context: &Context, roots: Vec<ModuleFile>, files: &BTreeSet<String>, analysis: &mut Analysis) {
+	let discovered = discover(context, roots, files, analysis);
+	let index = Arc::new(Index::new(discovered.iter().map(|file| &file.index)));
+	analysis.diagnostics.extend(index.diagnostics.iter().cloned());
+
+	let results: Vec<Result<(FileAnalysis, bool)>> = discovered.par_iter()
+		.map(|file| analyze_file(context, &index, file))
+		.collect();
+	analysis.types = index.types.clone();
+
+	for (file, result) in discovered.into_iter().zip(results) {
+		match result {
+			Ok((result, cached)) => {
+				if cached {
+					analysis.cached.push(file.file.path);
+				}
+				merge(analysis, result);
+			},
+			Err(err) => {
+				analysis.diagnostics.push(err);
+				analysis.skipped.push(file.file.path);
+			},
+		}
+	}
+	resolve_paths(&index, analysis);
+	link_calls(&index, analysis);
+}
+
+/// Resolve the paths of the calls to the canonical paths of the items, paths that don't name an item are removed
+fn resolve_paths(index: &Index, analysis: &mut Analysis) {
+	for function in &mut analysis.functions {
+		for node in function.graph.nodes.iter_mut().filter(|node| node.node_type == NodeType::Call) {
+			let segments: Option<Vec<String>> = node.path.as_ref().map(|path| path.split("::").map(String::from).collect());
+			node.resolve_path(segments.and_then(|segments| index.scopes.resolve(&function.module, &segments)));
+		}
+	}
+}
+
+/// Link the calls to the functions of the workspace they resolve to, calls that go to crates outside of the workspace are
+/// marked with the crate
+/// Method calls are linked to every method of the workspace they can end up in
+fn link_calls(index: &Index, analysis: &mut Analysis) {
+	let functions: BTreeSet<String> = analysis.functions.iter().map(Function::path).collect();
+
+	let mut links = Vec::new();
+	let mut externals = Vec::new();
+	for (position, function) in analysis.functions.iter().enumerate() {
+		let caller = function.path();
+		for node in &function.graph.nodes {
+			match (&node.node_type, &node.path) {
+				(NodeType::Call, Some(path)) => match functions.contains(path) {
+					true => links.push(Link {caller: caller.clone(), node: node.id, callee: path.clone()}),
+					false => {
+						let krate = path.split("::").next().unwrap_or(path);
+						if !index.scopes.modules.contains_key(krate) {
+							externals.push((position, node.id, krate.to_string()));
+						}
+					},
+				},
+				(NodeType::Method, _) => {
+					for candidate in node.candidates.iter().filter(|candidate| functions.contains(*candidate)) {
+						links.push(Link {caller: caller.clone(), node: node.id, callee: candidate.clone()});
+					}
+				},
+				_ => {},
+			}
+		}
+	}
+
+	for (position, id, krate) in externals {
+		if let Some(node) = analysis.functions[position].graph.get_node_mut(id) {
+			node.external = Some(krate);
+		}
+	}
+	links.dedup();
+	analysis.links = links;
+}
+
+/// Discover the files of the module tree, one level of the tree is read and indexed in parallel at a time
+/// Only the files that weren't ignored are discovered, they're returned in the order they were discovered in.
+fn discover(context: &Context, roots: Vec<ModuleFile>, files: &BTreeSet<String>, analysis: &mut Analysis) -> Vec<Discovered> {
+	let mut discovered = Vec::new();
+	let mut visited = BTreeSet::new();
+	let mut level = roots;
+
+	while !level.is_empty() {
+		// A file can be part of several crates Eg. when both `lib.rs` and `main.rs` declare it
+		let current: Vec<ModuleFile> = level.into_iter()
+			.filter(|file| visited.insert((file.krate.clone(), file.path.clone())))
+			.filter(|file| files.contains(&file.path))
+			.collect();
+
+		let results: Vec<Result<Discovered>> = current.par_iter()
+			.map(|file| index_file(context, file))
+			.collect();
+
+		// The declared modules are looked up every time since files can be added without the declaring file changing
+		level = Vec::new();
+		for (file, result) in current.into_iter().zip(results) {
+			match result {
+				Ok(result) => {
+					let (modules, missing) = module::resolve_modules(context.source, &file, &result.index.modules);
+					level.extend(modules);
+					analysis.diagnostics.extend(missing);
+					discovered.push(result);
+				},
+				Err(err) => {
+					analysis.diagnostics.push(err);
+					analysis.skipped.push(file.path);
+				},
+			}
+		}
+	}
+	discovered
+}
+
+/// Read and index a single file, the index is loaded from the cache when the file didn't change
+pub fn index_file(context: &Context, file: &ModuleFile) -> Result<Discovered> {
+	let content = context.source.read(&file.path).map_err(|err| Error::io(&file.path, err))?;
+	let cfg = context.cfg(&file.krate);
+	let key = Cache::key(&content, file, context.options, &cfg);
+
+	let index_key = Cache::derive(&key, "index");
+	if let Some(index) = context.cache.and_then(|cache| cache.load(&index_key)) {
+		return Ok(Discovered {file: file.clone(), content, key, index});
+	}
+
+	let syntax = syn::parse_file(&content).map_err(|err| Error::syntax(&file.path, err))?;
+	let index = index::index_file(file, &syntax, &cfg);
+	if let Some(cache) = context.cache {
+		cache.store(&index_key, &index);
+	}
+	Ok(Discovered {file: file.clone(), content, key, index})
+}
+
+/// Visit a single file with a state of its own
+/// The result is loaded from the cache when the file didn't change and the index still gives the same answers to its
+/// lookups, the boolean is true when it was.
+pub fn analyze_file(context: &Context, index: &Arc<Index>, file: &Discovered) -> Result<(FileAnalysis, bool)> {
+	let key = Cache::derive(&file.key, "analysis");
+	if let Some(result) = context.cache.and_then(|cache| cache.load::<FileAnalysis>(&key)) {
+		if result.lookups.iter().all(|(lookup, answer)| index.answer(lookup) == *answer) {
+			return Ok((result, true));
+		}
+	}
+
+	let syntax = syn::parse_file(&file.content).map_err(|err| Error::syntax(&file.file.path, err))?;
+	let mut state = State::new(context.options, &file.file, context.cfg(&file.file.krate), index.clone());
+	state.visit_file(&syntax);
+
+	let mut diagnostics = cfg::check_attributes(&file.file.path, &syntax);
+	diagnostics.extend(state.diagnostics);
+	let result = FileAnalysis {
+		path: file.file.path.clone(),
+		functions: state.functions,
+		diagnostics,
+		unsupported: state.unsupported,
+		lookups: state.lookups.into_inner().into_iter().map(|lookup| {
+			let answer = index.answer(&lookup);
+			(lookup, answer)
+		}).collect(),
+	};
+	if let Some(cache) = context.cache {
+		cache.store(&key, &result);
+	}
+	Ok((result, false))
+}
+
+/// Merge the result of a file into the analysis
+fn merge(analysis: &mut Analysis, file: FileAnalysis) {
+	if !analysis.parsed.contains(&file.path) {
+		analysis.parsed.push(file.path);
+	}
+	analysis.functions.extend(file.functions);
+	analysis.diagnostics.extend(file.diagnostics);
+	analysis.unsupported.extend(file.unsupported);
+}
@@ -0,0 +1,120 @@
+/*-------------
+/error.rs
+
+This file holds the errors of the application. Errors that stop the whole run are returned from `analyze`, errors that
+only affect a single file are collected as diagnostics in the `Analysis` so the rest of the project can still be analyzed.
+-------------*/
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Error {
+	/// No Cargo.toml was found at the path
+	ManifestNotFound { path: String },
+	/// The Cargo.toml has neither a `[package]` nor a `[workspace]`
+	NoPackageOrWorkspace { path: String },
+	/// The Cargo.toml couldn't be parsed
+	ManifestParse { path: String, message: String, span: Option<Span> },
+	/// The ara configuration of the project couldn't be parsed
+	ConfigParse { path: String, message: String, span: Option<Span> },
+	/// An ignore or include pattern isn't a valid glob
+	InvalidPattern { pattern: String, message: String },
+	/// Command line arguments that can't be used together
+	InvalidArgument { argument: String, message: String },
+	/// The file of a `mod x;` declaration couldn't be found
+	ModuleNotFound { path: String, module: String, candidates: Vec<String>, span: Span },
+	/// A file or directory couldn't be read
+	Io { path: String, message: String },
+	/// A file contains syntax that couldn't be parsed
+	UnsupportedSyntax { path: String, message: String, span: Span },
+	/// A cfg attribute couldn't be parsed, the item is kept as if it held
+	InvalidCfg { path: String, predicate: String, span: Span },
+	/// The rules of a `macro_rules!` macro of the workspace couldn't be parsed
+	MacroDefinition { path: String, name: String, message: String, span: Span },
+	/// A `macro_rules!` macro of the workspace couldn't be expanded
+	MacroExpansion { path: String, name: String, message: String, span: Span },
+}
+
+impl Error {
+	/// Create an io error for the given path
+	pub fn io(path: &str, err: std::io::Error) -> Error {
+		Error::Io {path: path.to_string(), message: err.to_string()}
+	}
+
+	/// Create a syntax error for the given path from a syn error
+	pub fn syntax(path: &str, err: syn::Error) -> Error {
+		let start = err.span().start();
+		Error::UnsupportedSyntax {path: path.to_string(), message: err.to_string(), span: Span::new(start.line, start.column + 1)}
+	}
+
+	/// Get the path of the file the error happened in, None for errors that aren't about a file
+	pub fn path(&self) -> Option<&str> {
+		match self {
+			Error::ManifestNotFound { path } => Some(path),
+			Error::NoPackageOrWorkspace { path } => Some(path),
+			Error::ManifestParse { path, .. } => Some(path),
+			Error::ConfigParse { path, .. } => Some(path),
+			Error::InvalidPattern { .. } => None,
+			Error::InvalidArgument { .. } => None,
+			Error::ModuleNotFound { path, .. } => Some(path),
+			Error::Io { path, .. } => Some(path),
+			Error::UnsupportedSyntax { path, .. } => Some(path),
+			Error::InvalidCfg { path, .. } => Some(path),
+			Error::MacroDefinition { path, .. } => Some(path),
+			Error::MacroExpansion { path, .. } => Some(path),
+		}
+	}
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::ManifestNotFound { path } => write!(f, "could not find manifest `{}`", path),
+			Error::NoPackageOrWorkspace { path } => write!(f, "manifest `{}` has neither a [package] nor a [workspace] table", path),
+			Error::ManifestParse { path, message, span: Some(span) } => write!(f, "could not parse manifest `{}:{}`: {}", path, span, message),
+			Error::ManifestParse { path, message, span: None } => write!(f, "could not parse manifest `{}`: {}", path, message),
+			Error::ConfigParse { path, message, span: Some(span) } => write!(f, "could not parse configuration `{}:{}`: {}", path, span, message),
+			Error::ConfigParse { path, message, span: None } => write!(f, "could not parse configuration `{}`: {}", path, message),
+			Error::InvalidPattern { pattern, message } => write!(f, "invalid pattern `{}`: {}", pattern, message),
+			Error::InvalidArgument { argument, message } => write!(f, "invalid argument `{}`: {}", argument, message),
+			Error::ModuleNotFound { path, module, candidates, span } => write!(f, "could not find module `{}` declared in `{}:{}`, looked for `{}`", module, path, span, candidates.join("`, `")),
+			Error::Io { path, message } => write!(f, "could not read `{}`: {}", path, message),
+			Error::UnsupportedSyntax { path, message, span } => write!(f, "unsupported syntax in `{}:{}`: {}", path, span, message),
+			Error::InvalidCfg { path, predicate, span } => write!(f, "could not parse `#[{}]` in `{}:{}`, it's treated as holding", predicate, path, span),
+			Error::MacroDefinition { path, name, message, span } => write!(f, "could not parse the rules of macro `{}!` defined in `{}:{}`: {}", name, path, span, message),
+			Error::MacroExpansion { path, name, message, span } => write!(f, "could not expand macro `{}!` in `{}:{}`: {}", name, path, span, message),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// A line and column in a file, both starting at 1
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span {
+	pub line: usize,
+	pub column: usize,
+}
+
+impl Span {
+	pub fn new(line: usize, column: usize) -> Span {
+		Span {line, column}
+	}
+
+	/// Get the span of a byte offset in the content
+	pub fn from_offset(content: &str, offset: usize) -> Span {
+		let before = &content[..offset.min(content.len())];
+		let line = before.matches('\n').count() + 1;
+		let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+		Span {line, column}
+	}
+}
+
+impl Display for Span {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}", self.line, self.column)
+	}
+}
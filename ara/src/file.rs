@@ -1,50 +1,148 @@
 /*-------------
 /file.rs
 
-This file is for the handling the creation of the file tree for the application.
+This file is for the handling the creation of the file tree for the application. Paths are matched relative to the root
+of the project with gitignore style patterns, and the `.gitignore`/`.ignore` files of the project are honored.
 -------------*/
-use std::fs;
-use tracing::error;
-use crate::{config, input::tree::Node};
-
-/// Grab all the files in the directory
-pub fn grab_files(directory: &mut Node) {
-    let paths = match fs::read_dir(directory.to_string()) {
-        Ok(paths) => paths,
-        Err(err) => {
-            error!("Error reading directory: {}", err);
-            return;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use crate::{analysis::Analysis, config::Options, error::{Error, Result}, input::{source::Source, tree::Node}};
+
+/// The ignore files that are read in every directory, later files win over earlier ones
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Decides which paths of the project are analyzed
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// The ignore patterns of the options, these win over the ignore files
+    ignore: Gitignore,
+    /// Files have to match one of these when there are any
+    include: Option<GlobSet>,
+    /// Read the ignore files of the project
+    gitignore: bool,
+}
+
+impl Filter {
+    /// Create the filter from the ignore and include patterns of the options
+    pub fn new(options: &Options) -> Result<Filter> {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in &options.ignore {
+            builder.add_line(None, pattern).map_err(|err| Error::InvalidPattern { pattern: pattern.clone(), message: err.to_string() })?;
         }
-    };
+        let ignore = builder.build().map_err(|err| Error::InvalidPattern { pattern: options.ignore.join(" "), message: err.to_string() })?;
 
-    for path in paths {
-        let is_dir = match path.as_ref() {
-            Ok(path) => path.path().is_dir(),
-            Err(err) => {
-                error!("Error seeing if path is a dir: {}", err);
-                continue;
+        let include = if options.include.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in &options.include {
+                let glob = Glob::new(pattern.trim_start_matches('/')).map_err(|err| Error::InvalidPattern { pattern: pattern.clone(), message: err.to_string() })?;
+                builder.add(glob);
             }
+            Some(builder.build().map_err(|err| Error::InvalidPattern { pattern: options.include.join(" "), message: err.to_string() })?)
         };
 
-        let path = match path {
-            Ok(path) => path.path().to_string_lossy().to_string(),
-            Err(err) => {
-                error!("Error reading path: {}", err);
-                continue;
+        Ok(Filter {ignore, include, gitignore: options.gitignore})
+    }
+
+    /// Read the ignore files of the directory, None is returned when there are none
+    pub fn read_ignore_files(&self, source: &dyn Source, directory: &str) -> Option<Gitignore> {
+        if !self.gitignore {
+            return None;
+        }
+
+        let mut builder = GitignoreBuilder::new(directory);
+        let mut found = false;
+        for file in IGNORE_FILES {
+            let path = if directory.is_empty() { file.to_string() } else { format!("{}/{}", directory, file) };
+            if let Ok(contents) = source.read(&path) {
+                found = true;
+                // Lines that aren't valid globs are ignored the same way git does
+                contents.lines().for_each(|line| { let _ = builder.add_line(None, line); });
             }
-        };
+        }
+
+        if !found {
+            return None;
+        }
+        builder.build().ok()
+    }
+
+    /// Check if the path is ignored, the ignore files are ordered from the root to the directory of the path
+    /// Files that are whitelisted by the ignore rules still have to match the include globs.
+    pub fn is_ignored(&self, path: &str, is_dir: bool, ignore_files: &[Gitignore]) -> bool {
+        if path.rsplit('/').next() == Some(".git") {
+            return true;
+        }
+
+        // The first rule that matches decides, the ignore option comes before the ignore files
+        for rules in std::iter::once(&self.ignore).chain(ignore_files.iter().rev()) {
+            match rules.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => break,
+                Match::None => {},
+            }
+        }
+
+        match &self.include {
+            Some(include) if !is_dir => !include.is_match(path),
+            _ => false,
+        }
+    }
+
+    /// Check if a single path relative to the project is ignored the same way `grab_files` would ignore it
+    /// The path is ignored when it or any of its parent directories is.
+    pub fn is_path_ignored(&self, source: &dyn Source, path: &str, is_dir: bool) -> bool {
+        let mut ignore_files: Vec<Gitignore> = self.read_ignore_files(source, "").into_iter().collect();
+        let components: Vec<&str> = path.split('/').collect();
+        for i in 1..=components.len() {
+            let current = components[..i].join("/");
+            let last = i == components.len();
+            if self.is_ignored(&current, !last || is_dir, &ignore_files) {
+                return true;
+            }
+            if !last {
+                ignore_files.extend(self.read_ignore_files(source, &current));
+            }
+        }
+        false
+    }
+}
+
+/// Grab all the files in the directory, the ignored paths are added to the skipped files of the analysis
+pub fn grab_files(directory: &mut Node, source: &dyn Source, filter: &Filter, ignore_files: &mut Vec<Gitignore>, analysis: &mut Analysis) {
+    let entries = match source.read_dir(&directory.get_key()) {
+        Ok(entries) => entries,
+        Err(err) => {
+            analysis.diagnostics.push(Error::io(&directory.get_key(), err));
+            return;
+        }
+    };
+
+    let ignore_file = filter.read_ignore_files(source, &directory.get_key());
+    let pushed = ignore_file.is_some();
+    ignore_files.extend(ignore_file);
+
+    for entry in entries {
+        let path = entry.path;
 
-        if config::APP.ignore.iter().any(|ignore| path.contains(ignore)) {
+        if filter.is_ignored(&path, entry.is_dir, ignore_files) {
+            analysis.skipped.push(path);
             continue;
         }
 
-        if is_dir {
+        if entry.is_dir {
             let mut node = Node::new();
             node.add_key(&path);
-            grab_files(&mut node);
+            grab_files(&mut node, source, filter, ignore_files, analysis);
             directory.add_child(node);
         } else {
             directory.add_key(&path);
         }
     }
+
+    if pushed {
+        ignore_files.pop();
+    }
 }
@@ -3,9 +3,10 @@
 
 This file is for handling edge operations in a graph.
 -------------*/
+use serde_derive::{Deserialize, Serialize};
 use std::fmt::{self, Debug, Formatter};
 
-#[derive(Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Edge {
 	pub from: i16,
 	pub to: i16,
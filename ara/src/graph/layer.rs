@@ -4,7 +4,7 @@
 This file is tricky. Its is used in the graph and allows us to build the call layers to know how to connect them back to each other.
 -------------*/
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct Layer {
 	pub id: i16,
 	pub layer: i16,
@@ -3,57 +3,57 @@
 
 This file is for handling graph operations.
 -------------*/
+use serde_derive::{Deserialize, Serialize};
 use std::fmt::Debug;
 use crate::graph::{node::{Node, NodeType}, edge::Edge, layer::Layer};
-use crate::log::debug::{error, warn, debug};
+use crate::log::Log;
 
 pub mod node;
 pub mod edge;
 pub mod layer;
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Graph {
 	pub nodes: Vec<Node>,
 	pub edges: Vec<Edge>,
+	#[serde(skip)]
 	pub calls: Vec<Layer>,
+	#[serde(skip)]
 	pub layer: i16,
+	#[serde(skip)]
 	pub layer_args: i16,
+	#[serde(skip)]
+	pub log: Log,
 }
 
 impl Graph {
-	pub fn new() -> Graph {
-		Graph {nodes: Vec::new(), edges: Vec::new(), calls: Vec::new(), layer: 0, layer_args: 0}
+	pub fn new(log: Log) -> Graph {
+		Graph {nodes: Vec::new(), edges: Vec::new(), calls: Vec::new(), layer: 0, layer_args: 0, log}
 	}
-	/// This method adds a node to the graph and returns a reference to the node.
-	pub fn add_node(&mut self, mut node: Node) -> &Node {
+	/// This method adds a node to the graph and returns the id of the node.
+	pub fn add_node(&mut self, mut node: Node) -> i16 {
 		// Update the id of the node
 		let id = self.get_node_len();
 		node.update_id(id);
 
-		debug(&format!("Calls {:?}", self.calls).to_string());
-		debug(&format!("Adding node: {:?}", node).to_string());
+		self.log.debug(&format!("Calls {:?}", self.calls).to_string());
+		self.log.debug(&format!("Adding node: {:?}", node).to_string());
 
 		self.calls.push(Layer::new(id, self.layer, self.layer_args));
 		self.nodes.push(node);
-		match self.nodes.last() {
-			Some(node) => node,
-			None => {
-				error(&"Node not found".to_string());
-				panic!("Exiting...");
-			},
-		}
+		id
 	}
 	/// This method adds an edge to the graph.
 	pub fn add_edge(&mut self, to: i16) {
 		let from = match self.get_offset() {
 			Some(offset) => offset,
 			None => {
-				warn(&format!("Failed to get offset for to {}", to));
+				self.log.warn(&format!("Failed to get offset for to {}", to));
 				return
 			},
 		};
 
-		debug(&format!("Adding edge from: {} to: {}", from.id, to));
+		self.log.debug(&format!("Adding edge from: {} to: {}", from.id, to));
 
 		self.edges.push(Edge::new(from.id, to));
 	}
@@ -62,7 +62,7 @@ impl Graph {
 		let layer_id = match self.get_last_layer(){
 			Some(layer) => layer.id,
 			None => {
-				warn(&format!("Failed to get last layer and couldn't add {}", literal));
+				self.log.warn(&format!("Failed to get last layer and couldn't add {}", literal));
 				return
 			},
 		};
@@ -71,31 +71,32 @@ impl Graph {
 			node.add_literal(literal);
 		}
 	}
-	/// This method adds a local to a node.
-	pub fn add_local(&mut self, local: &str) {
-		let node_id = match self.get_first() {
-			Some(id) => id,
+	/// This method adds a local to the node of the expression it is bound to, which is the last node since `since` on the
+	/// current layer and argument level Eg. `parse` of `let id = id.parse()`
+	pub fn add_local(&mut self, since: i16, local: &str) {
+		let node_id = match self.calls.iter().rev().find(|calls| calls.id >= since && calls.layer == self.layer && calls.args == self.layer_args) {
+			Some(calls) => calls.id,
 			None => {
-				warn(&format!("Failed to get first node and couldn't add {}", local));
+				self.log.warn(&format!("Failed to get the node of the expression and couldn't add {}", local));
 				return
 			},
 		};
 
-		if let Some(node) = self.get_node_mut(*node_id) {
+		if let Some(node) = self.get_node_mut(node_id) {
 			node.add_local(local);
 		}
 	}
 	/// This function is used to grab the offset of the last layer.
 	/// Example:
 	/// function {
-	/// 	call(arg1, arg2)
+	///     call(arg1, arg2)
 	/// }
 	/// Function is layer 0, Call is layer 1, arg1 and arg2 is layer 2
 	pub fn get_offset(&self) -> Option<&Layer> {
 		let last_call = match self.get_last_layer() {
 			Some(call) => call,
 			None => {
-				warn(&"Failed to get last call".to_string());
+				self.log.warn(&"Failed to get last call".to_string());
 				return None
 			},
 		};
@@ -103,7 +104,7 @@ impl Graph {
 		let most_recent_node = match self.get_node(last_call.id) {
 			Some(node) => node,
 			None => {
-				warn(&"Failed to get most recent call".to_string());
+				self.log.warn(&"Failed to get most recent call".to_string());
 				return None
 			},
 		};
@@ -114,27 +115,35 @@ impl Graph {
 				let node_type = match self.get_node(calls.id) {
 					Some(node) => &node.node_type,
 					None => {
-						warn(&"Failed to get node type".to_string());
+						self.log.warn(&"Failed to get node type".to_string());
 						return None
 					}
 				};
 		
 				if (node_type == &NodeType::Local ||  node_type == &NodeType::Call) 
 				&& calls.args == last_call.args && calls.layer == last_call.layer {
-					return Some(&calls);
+					return Some(calls);
 				}
 			}
 		}
     
-		// If its not a method we can iterate through the calls and check layers
+		// An argument belongs to the most recent call one argument level up Eg. the closure that is passed to `map`
+		if last_call.args > 0 {
+			let parent = self.calls.iter().rev().skip(1).find(|calls| calls.layer == last_call.layer && calls.args == last_call.args - 1);
+			if parent.is_some() {
+				return parent;
+			}
+		}
+
+		// If its not a method we can iterate through the calls and check layers, the first call has no layer before it
 		for (i, calls) in self.calls.iter().enumerate() {
 			let offset = i + 1;
 
 			if offset == self.calls.len() {
-				return Some(&self.calls[self.calls.len() - 2])
+				return self.calls.iter().rev().nth(1)
 			}
 			if calls.args != self.calls[offset].args && calls.layer == self.calls[offset].layer {
-				return Some(&calls);
+				return Some(calls);
 			}
 		}
 		None
@@ -143,15 +152,6 @@ impl Graph {
 	pub fn get_last_layer(&self) -> Option<&Layer> {
 		self.calls.last()
 	}
-	/// Get the first call in the call stack where the layer is the current layer.
-	pub fn get_first(&self) -> Option<&i16> {
-		for calls in self.calls.iter() {
-			if calls.layer == self.layer {
-				return Some(&calls.id);
-			}
-		}
-		None
-	}
 	/// Get a node by its id.
 	pub fn get_node_mut(&mut self, id: i16) -> Option<&mut Node> {
 		self.nodes.iter_mut().find(|node| node.id == id)
@@ -168,15 +168,6 @@ impl Graph {
 	pub fn get_node_len(&self) -> i16 {
 		self.nodes.len() as i16
 	}
-	/// Check to see if a local exists in the graph.
-	pub fn local_exists(&self, local: &str) -> bool {
-		for node in &self.nodes {
-			if node.local == local {
-				return true;
-			}
-		}
-		false
-	}
 	/// Clear all the calls from the current layer.
 	pub fn clear_calls_layer(&mut self) {
 		self.calls.retain(|v| v.layer != self.layer);
@@ -3,9 +3,10 @@
 
 This file is for the node operations in the graph.
 -------------*/
+use serde_derive::{Deserialize, Serialize};
 use std::fmt::{self, Debug, Formatter};
 
-#[derive(Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Node {
 	pub id: i16,
 	pub key: String,
@@ -13,11 +14,26 @@ pub struct Node {
 	pub literal: Option<String>,
 	pub doc: Vec<String>,
 	pub node_type: NodeType,
+	/// The canonical path of the item a call refers to Eg. "app::routes::get_health", None when it isn't an item
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub path: Option<String>,
+	/// How a method call is dispatched, None when the type of the receiver isn't known
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub dispatch: Option<Dispatch>,
+	/// The paths of the functions a method call can end up in Eg. "<app::repo::PgRepo as UserRepo>::find_user"
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub candidates: Vec<String>,
+	/// A call was resolved to the item it refers to, or a method call to the methods of the workspace it can end up in
+	#[serde(default)]
+	pub resolved: bool,
+	/// The crate outside of the workspace that a call goes to, there's no definition to link it to Eg. "serde_json"
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub external: Option<String>,
 }
 
 impl Node {
 	pub fn new(key: &str, node_type: NodeType) -> Node {
-		Node {id: 0, local: String::new(), literal: None, doc: Vec::new(), key: key.to_string(), node_type}
+		Node {id: 0, local: String::new(), literal: None, doc: Vec::new(), key: key.to_string(), node_type, path: None, dispatch: None, candidates: Vec::new(), resolved: false, external: None}
 	}
 	pub fn update_id(&mut self, id: i16) {
 		self.id = id;
@@ -28,6 +44,19 @@ impl Node {
 	pub fn add_literal(&mut self, literal: &str) {
 		self.literal = Some(literal.to_string());
 	}
+	pub fn add_path(&mut self, path: Option<String>) {
+		self.path = path;
+	}
+	pub fn add_dispatch(&mut self, dispatch: Dispatch, candidates: Vec<String>) {
+		self.dispatch = Some(dispatch);
+		self.candidates = candidates;
+		self.resolved = true;
+	}
+	/// Set the canonical path of a call once every file is visited, calls without one are unresolved
+	pub fn resolve_path(&mut self, path: Option<String>) {
+		self.resolved = path.is_some();
+		self.path = path;
+	}
 	pub fn add_comments(&mut self, doc: Vec<String>) -> &mut Node {
 		self.doc = doc;
 		self
@@ -36,11 +65,24 @@ impl Node {
 
 impl Debug for Node {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-		write!(f, "Id: {} Key: {} Local: {} Literal: {:?} Type: {:?}", self.id, self.key, self.local, self.literal, self.node_type)
+		write!(f, "Id: {} Key: {} Local: {} Literal: {:?} Type: {:?}", self.id, self.key, self.local, self.literal, self.node_type)?;
+		if let Some(path) = &self.path {
+			write!(f, " Path: {}", path)?;
+		}
+		if let Some(dispatch) = &self.dispatch {
+			write!(f, " Dispatch: {:?} Candidates: {:?}", dispatch, self.candidates)?;
+		}
+		if let Some(external) = &self.external {
+			write!(f, " External: {}", external)?;
+		}
+		match (&self.node_type, self.resolved) {
+			(NodeType::Call | NodeType::Method, false) => write!(f, " Unresolved"),
+			_ => Ok(()),
+		}
 	}
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum NodeType {
 	Function,
 	Method,
@@ -50,4 +92,16 @@ pub enum NodeType {
 	Struct,
 	Paren,
 	Tuple,
-}
\ No newline at end of file
+	Closure,
+}
+/// How a method call ends up in its implementation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Dispatch {
+	/// The receiver has a concrete type so the call goes to a single method
+	Static,
+	/// The receiver is generic over a trait Eg. `impl UserRepo`, the call is resolved at compile time to one of the
+	/// implementations
+	Generic,
+	/// The receiver is a trait object Eg. `Arc<dyn UserRepo>`, the call goes through the vtable
+	Dynamic,
+}
@@ -0,0 +1,493 @@
+/*-------------
+/index.rs
+
+This file is for the index of the workspace. Before any function is visited every file of the module tree is indexed
+for what the other files need to know about it, the modules it declares, the `macro_rules!` macros it defines, its
+traits and impl blocks, its data types and the types its functions return. The indexes of the files are combined into a single index that every file is visited with, which
+is where method calls are resolved to the implementations they can end up in. The lookups a file makes in the index are
+kept with its result, so the file only has to be visited again when the answer to one of them changes.
+-------------*/
+use std::collections::BTreeMap;
+use serde_derive::{Deserialize, Serialize};
+use syn::{visit::{self, Visit}, ImplItem, Item, TraitItem};
+use crate::analysis;
+use crate::cfg::Cfg;
+use crate::error::{Error, Span};
+use crate::graph::node::Dispatch;
+use crate::module::{self, Declaration, ModuleFile};
+use crate::parse::expand::MacroRules;
+use crate::parse::utils;
+use crate::scope::{self, ModuleScope, Scopes};
+use crate::types::{self, TypeDefinition, TypeKind, TypeRegistry};
+
+/// What a single file adds to the index
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FileIndex {
+	/// The modules that are declared in the file
+	pub modules: Vec<Declaration>,
+	/// The `macro_rules!` macros that are defined in the file
+	pub macros: Vec<MacroDefinition>,
+	/// The traits that are defined in the file
+	pub traits: Vec<TraitDefinition>,
+	/// The impl blocks in the file
+	pub impls: Vec<ImplDefinition>,
+	/// The structs, enums, unions and type aliases that are defined in the file
+	pub types: Vec<TypeDefinition>,
+	/// The items and imports of the module of the file and of its inline modules
+	pub scopes: Vec<ModuleScope>,
+	/// The types that the functions and methods in the file return
+	pub returns: Vec<ReturnType>,
+}
+
+/// The type a function or a method returns as it is written
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReturnType {
+	/// The key of the function Eg. "crate::users::load"
+	pub key: String,
+	/// The canonical path of the module the type is written in Eg. "app::users"
+	pub module: String,
+	pub ty: TypeRef,
+}
+
+/// A `macro_rules!` macro that is defined in the workspace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MacroDefinition {
+	pub name: String,
+	/// The crate the macro is defined in
+	pub krate: String,
+	/// The file the macro is defined in
+	pub file: String,
+	/// The macro has `#[macro_export]` so the other crates can use it
+	pub exported: bool,
+	/// The rules of the macro as tokens
+	pub body: String,
+	/// Where the name of the macro is in the file
+	pub span: Span,
+}
+
+/// A trait that is defined in the workspace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TraitDefinition {
+	/// The path of the trait Eg. "crate::repo::UserRepo"
+	pub path: String,
+	pub name: String,
+	pub krate: String,
+	pub file: String,
+	pub methods: Vec<TraitMethod>,
+}
+
+/// A method that is declared in a trait
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TraitMethod {
+	pub name: String,
+	/// The method has a default body, impls that don't define the method use it
+	pub default: bool,
+}
+
+impl TraitDefinition {
+	/// Get the key of a method of the trait, default bodies are visited with this key Eg. "crate::repo::UserRepo::find"
+	pub fn method_key(&self, method: &str) -> String {
+		format!("{}::{}", self.path, method)
+	}
+
+	/// Get the path of a method of the trait with the name of its crate Eg. "app::repo::UserRepo::find"
+	pub fn method_path(&self, method: &str) -> String {
+		analysis::crate_path(&self.method_key(method), &self.krate)
+	}
+
+	/// Get the canonical path of the trait Eg. "app::repo::UserRepo"
+	pub fn canonical_path(&self) -> String {
+		analysis::crate_path(&self.path, &self.krate)
+	}
+}
+
+/// An impl block of the workspace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImplDefinition {
+	/// The type the impl block is for Eg. "crate::repo::PgRepo"
+	pub self_type: String,
+	/// The canonical path of the module the impl block is in Eg. "app::repo"
+	pub module: String,
+	/// The canonical path of the type, it's resolved once the scopes of every file are known Eg. "app::repo::PgRepo"
+	#[serde(skip)]
+	pub path: String,
+	/// The trait as it is written in the impl block Eg. "UserRepo"
+	pub trait_name: Option<String>,
+	/// The canonical path of the trait, it's resolved once the scopes of every file are known Eg. "app::repo::UserRepo"
+	#[serde(skip)]
+	pub trait_path: Option<String>,
+	pub krate: String,
+	pub file: String,
+	/// The names of the methods that are defined in the impl block
+	pub methods: Vec<String>,
+}
+
+impl ImplDefinition {
+	/// Get the key of a method that is defined in the impl block
+	pub fn method_key(&self, method: &str) -> String {
+		utils::get_method_key(&self.self_type, self.trait_name.as_deref(), method)
+	}
+
+	/// Get the path of a method that is defined in the impl block with the name of its crate
+	/// Eg. "<app::repo::PgRepo as UserRepo>::find_user"
+	pub fn method_path(&self, method: &str) -> String {
+		analysis::crate_path(&self.method_key(method), &self.krate)
+	}
+
+	/// Check if the impl block implements the trait, traits are matched by their canonical paths
+	fn implements(&self, definition: &TraitDefinition) -> bool {
+		self.trait_path.as_ref().is_some_and(|path| *path == definition.canonical_path())
+	}
+}
+
+/// What is known about the type of a receiver
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TypeRef {
+	/// A type Eg. "crate::repo::PgRepo"
+	Concrete(String),
+	/// A trait object of the traits Eg. `dyn UserRepo`
+	Dyn(Vec<String>),
+	/// A type that is only known by the traits it implements Eg. `impl UserRepo` or `T: UserRepo`
+	Bounded(Vec<String>),
+	/// An `Option` or a `Result` of a type, `?` gets the type inside of it Eg. `Result<User, Error>`
+	Wrapped(Box<TypeRef>),
+}
+
+/// A lookup of a file in the index
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lookup {
+	/// A method on a receiver
+	Method(TypeRef, String),
+	/// The return type of a function or method by its canonical path
+	ReturnType(String),
+	/// The canonical path of an item that is written in a module
+	Path(String, Vec<String>),
+	/// A field of a type
+	Field(String, String),
+	/// The canonical paths of a type that is written in a module
+	Type(String, TypeRef),
+	/// A macro by the segments of the invocation, the crate and the file it's in
+	Macro(Vec<String>, String, String),
+}
+
+/// The index of the whole workspace
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+	pub macros: Vec<MacroDefinition>,
+	/// The parsed rules of the macros at the same position as their definition, None when the rules can't be parsed
+	pub rules: Vec<Option<MacroRules>>,
+	/// The macros whose rules can't be parsed, they're reported at their definition instead of at every invocation
+	pub diagnostics: Vec<Error>,
+	pub traits: Vec<TraitDefinition>,
+	pub impls: Vec<ImplDefinition>,
+	pub types: TypeRegistry,
+	/// The types that the functions and methods of the workspace return by the path of the function
+	/// Eg. "app::users::load" or "<app::repo::PgRepo as UserRepo>::find"
+	pub returns: BTreeMap<String, TypeRef>,
+	/// The paths of the calls are resolved after the files are visited, so files don't have to be visited again when the
+	/// items of another file change
+	pub scopes: Scopes,
+}
+
+impl Index {
+	/// Combine the indexes of the files
+	pub fn new<'a>(files: impl Iterator<Item = &'a FileIndex>) -> Index {
+		let files: Vec<&FileIndex> = files.collect();
+		let mut index = Index::default();
+		for module in files.iter().flat_map(|file| &file.scopes) {
+			index.scopes.insert(module.clone());
+		}
+
+		for file in files {
+			for definition in &file.macros {
+				let rules = definition.body.parse().map_err(|err: proc_macro2::LexError| err.to_string()).and_then(MacroRules::parse);
+				match rules {
+					Ok(rules) => index.rules.push(Some(rules)),
+					Err(message) => {
+						index.rules.push(None);
+						index.diagnostics.push(Error::MacroDefinition {path: definition.file.clone(), name: definition.name.clone(), message, span: definition.span});
+					},
+				}
+				index.macros.push(definition.clone());
+			}
+			index.traits.extend(file.traits.iter().cloned());
+			for implementation in &file.impls {
+				let path = index.scopes.resolve_type(&implementation.module, &TypeRef::Concrete(implementation.self_type.clone()));
+				let path = match path {
+					TypeRef::Concrete(path) => path,
+					_ => implementation.self_type.clone(),
+				};
+				let trait_path = implementation.trait_name.as_ref().map(|name| index.scopes.resolve_trait(&implementation.module, name));
+				index.impls.push(ImplDefinition {path, trait_path, ..implementation.clone()});
+			}
+			for definition in &file.types {
+				index.types.insert(definition.clone());
+			}
+			for returns in &file.returns {
+				let krate = returns.module.split("::").next().unwrap_or_default();
+				index.returns.insert(analysis::crate_path(&returns.key, krate), index.scopes.resolve_type(&returns.module, &returns.ty));
+			}
+		}
+		index
+	}
+
+	/// Get the answer to a lookup, the result of a file can come from the cache when every answer is the same as when the
+	/// file was visited
+	pub fn answer(&self, lookup: &Lookup) -> String {
+		let answer = match lookup {
+			Lookup::Method(receiver, method) => serde_json::to_string(&self.resolve_method(receiver, method)),
+			Lookup::ReturnType(key) => serde_json::to_string(&self.return_type(key)),
+			Lookup::Path(module, segments) => serde_json::to_string(&self.scopes.resolve(module, segments)),
+			Lookup::Field(self_type, field) => serde_json::to_string(&self.field_type(self_type, field)),
+			Lookup::Type(module, ty) => serde_json::to_string(&self.scopes.resolve_type(module, ty)),
+			Lookup::Macro(segments, krate, file) => serde_json::to_string(&self.find_macro(segments, krate, file).map(|(definition, _)| definition)),
+		};
+		answer.unwrap_or_default()
+	}
+
+	/// Find the macro that an invocation in the crate refers to Eg. `["routes"]` or `["shared", "routes"]`
+	/// Macros of the crate itself are used first, preferring the ones in the same file, then exported macros of the
+	/// other crates. The rules are None when they couldn't be parsed.
+	pub fn find_macro(&self, segments: &[String], krate: &str, file: &str) -> Option<(&MacroDefinition, Option<&MacroRules>)> {
+		let name = segments.last()?;
+		let first = segments.first()?;
+		let local = segments.len() == 1 || ["crate", "self", "super"].contains(&first.as_str()) || first == krate;
+
+		let mut candidates = self.macros.iter().enumerate().filter(|(_, definition)| &definition.name == name);
+		let mut found = None;
+		if local {
+			let same_crate: Vec<(usize, &MacroDefinition)> = candidates.clone().filter(|(_, definition)| definition.krate == krate).collect();
+			found = same_crate.iter().find(|(_, definition)| definition.file == file).or(same_crate.first()).copied();
+		}
+		let (position, definition) = found.or_else(|| candidates.find(|(_, definition)| definition.exported && (segments.len() == 1 || definition.krate == *first)))?;
+		Some((definition, self.rules.get(position).and_then(Option::as_ref)))
+	}
+
+	/// Resolve a method call on a receiver to the functions it can end up in
+	/// A concrete type gets its inherent method, or the method of the trait it implements. Trait objects and generics get
+	/// the method of the trait followed by the method of every impl of the trait. None is returned when the method isn't
+	/// found in the workspace Eg. methods of the standard library.
+	pub fn resolve_method(&self, receiver: &TypeRef, method: &str) -> Option<(Dispatch, Vec<String>)> {
+		let traits = match receiver {
+			TypeRef::Concrete(self_type) => return self.resolve_concrete(self_type, method).map(|key| (Dispatch::Static, vec![key])),
+			TypeRef::Dyn(traits) | TypeRef::Bounded(traits) => traits,
+			// The methods of `Option` and `Result` are in the standard library
+			TypeRef::Wrapped(_) => return None,
+		};
+
+		let mut candidates = Vec::new();
+		let declared = self.traits.iter()
+			.filter(|definition| traits.contains(&definition.canonical_path()))
+			.filter(|definition| definition.methods.iter().any(|found| found.name == method));
+		for definition in declared {
+			// Impls without the method use the default body, which is the method of the trait itself
+			candidates.push(definition.method_path(method));
+			for implementation in self.impls.iter().filter(|implementation| implementation.implements(definition)) {
+				let path = implementation.method_path(method);
+				if implementation.methods.iter().any(|found| found == method) && !candidates.contains(&path) {
+					candidates.push(path);
+				}
+			}
+		}
+		if candidates.is_empty() {
+			return None;
+		}
+
+		let dispatch = if matches!(receiver, TypeRef::Dyn(_)) { Dispatch::Dynamic } else { Dispatch::Generic };
+		Some((dispatch, candidates))
+	}
+
+	/// Get the type a function or a method of the workspace returns by its path
+	pub fn return_type(&self, key: &str) -> Option<&TypeRef> {
+		self.returns.get(key)
+	}
+
+	/// Get the type of a field of a struct of the type registry by the canonical path of the struct
+	/// Eg. the field "pool" of "app::state::AppState".
+	pub fn field_type(&self, self_type: &str, field: &str) -> Option<TypeRef> {
+		let definition = self.types.types.get(self_type).filter(|definition| definition.kind != TypeKind::Alias)?;
+		let field = definition.fields.iter().find(|found| found.name == field)?;
+		let ty: syn::Type = syn::parse_str(&field.ty).ok()?;
+
+		// The generic parameters of the struct aren't known
+		let bounds: Vec<(String, Vec<String>)> = definition.generics.iter()
+			.map(|parameter| (parameter.split(':').next().unwrap_or(parameter).trim().to_string(), Vec::new()))
+			.collect();
+		let module = definition.path.rsplit_once("::").map(|(module, _)| module).unwrap_or(&definition.path);
+		let ty = utils::get_type_ref(&ty, module, &bounds, Some(&TypeRef::Concrete(definition.path.clone())))?;
+		Some(self.scopes.resolve_type(module, &ty))
+	}
+
+	/// Find the method of a type by its canonical path, inherent methods come before the methods of the traits it implements
+	fn resolve_concrete(&self, self_type: &str, method: &str) -> Option<String> {
+		let impls: Vec<&ImplDefinition> = self.impls.iter().filter(|implementation| implementation.path == self_type).collect();
+		let defined = impls.iter()
+			.filter(|implementation| implementation.methods.iter().any(|found| found == method))
+			.min_by_key(|implementation| implementation.trait_name.is_some());
+		if let Some(implementation) = defined {
+			return Some(implementation.method_path(method));
+		}
+
+		// The method can come from the default body of a trait the type implements
+		impls.iter()
+			.flat_map(|implementation| self.traits.iter().filter(|definition| implementation.implements(definition)))
+			.find(|definition| definition.methods.iter().any(|found| found.name == method && found.default))
+			.map(|definition| definition.method_path(method))
+	}
+}
+
+/// Index a file, items whose cfg attributes don't hold are left out
+/// The items that `macro_rules!` macros expand to aren't indexed since the macros are only known once every file is
+/// indexed, the functions they generate are visited but the calls to them aren't resolved.
+pub fn index_file(file: &ModuleFile, syntax: &syn::File, cfg: &Cfg) -> FileIndex {
+	let mut index = FileIndex {modules: module::declared_modules(file, syntax, cfg), ..FileIndex::default()};
+	if cfg.is_enabled(&syntax.attrs) {
+		collect_items(file, &mut file.module.clone(), &syntax.items, cfg, &mut index);
+	}
+	index
+}
+
+/// Walk the items for `macro_rules!` definitions, traits, impl blocks, data types and the scope of the module, inline
+/// modules are searched as well
+fn collect_items(file: &ModuleFile, module: &mut Vec<String>, items: &[Item], cfg: &Cfg, index: &mut FileIndex) {
+	// Items are registered with the name of their crate instead of `crate`
+	let path = scope::module_path(&file.krate, module);
+	let mut module_scope = ModuleScope::new(&path);
+	for item in items {
+		if cfg.is_enabled(utils::get_item_attrs(item)) {
+			module_scope.add_item(item);
+		}
+		TypeCollector {file, cfg, prefix: path.clone(), types: &mut index.types}.visit_item(item);
+
+		match item {
+			Item::Macro(item) if cfg.is_enabled(&item.attrs) && item.mac.path.is_ident("macro_rules") => {
+				let ident = match &item.ident {
+					Some(ident) => ident,
+					None => continue,
+				};
+				let start = ident.span().start();
+				index.macros.push(MacroDefinition {
+					name: ident.to_string(),
+					span: Span::new(start.line, start.column + 1),
+					krate: file.krate.clone(),
+					file: file.path.clone(),
+					exported: item.attrs.iter().any(|attr| attr.path().is_ident("macro_export")),
+					body: item.mac.tokens.to_string(),
+				});
+			},
+			Item::Fn(item) if cfg.is_enabled(&item.attrs) => {
+				let key = format!("{}::{}", module.join("::"), item.sig.ident);
+				let bounds = utils::get_generic_bounds(&item.sig.generics);
+				index.returns.extend(utils::get_return_type(&item.sig, &module.join("::"), &bounds, None).map(|ty| ReturnType {key, module: path.clone(), ty}));
+			},
+			Item::Trait(item) if cfg.is_enabled(&item.attrs) => {
+				let definition = TraitDefinition {
+					path: format!("{}::{}", module.join("::"), item.ident),
+					name: item.ident.to_string(),
+					krate: file.krate.clone(),
+					file: file.path.clone(),
+					methods: Vec::new(),
+				};
+				let self_type = TypeRef::Bounded(vec![item.ident.to_string()]);
+				let methods = item.items.iter().filter_map(|item| match item {
+					TraitItem::Fn(method) if cfg.is_enabled(&method.attrs) => {
+						let bounds = utils::get_generic_bounds(&method.sig.generics);
+						let returns = utils::get_return_type(&method.sig, &module.join("::"), &bounds, Some(&self_type));
+						index.returns.extend(returns.map(|ty| ReturnType {key: definition.method_key(&method.sig.ident.to_string()), module: path.clone(), ty}));
+						Some(TraitMethod {name: method.sig.ident.to_string(), default: method.default.is_some()})
+					},
+					_ => None,
+				}).collect();
+				index.traits.push(TraitDefinition {methods, ..definition});
+			},
+			Item::Impl(item) if cfg.is_enabled(&item.attrs) => {
+				let (self_type, trait_name) = utils::get_impl_names(&module.join("::"), item);
+				let concrete = TypeRef::Concrete(self_type.clone());
+				let impl_bounds = utils::get_generic_bounds(&item.generics);
+				let methods = item.items.iter().filter_map(|item| match item {
+					ImplItem::Fn(method) if cfg.is_enabled(&method.attrs) => {
+						let mut bounds = impl_bounds.clone();
+						bounds.extend(utils::get_generic_bounds(&method.sig.generics));
+						let key = utils::get_method_key(&self_type, trait_name.as_deref(), &method.sig.ident.to_string());
+						index.returns.extend(utils::get_return_type(&method.sig, &module.join("::"), &bounds, Some(&concrete)).map(|ty| ReturnType {key, module: path.clone(), ty}));
+						Some(method.sig.ident.to_string())
+					},
+					_ => None,
+				}).collect();
+				index.impls.push(ImplDefinition {self_type, module: path.clone(), path: String::new(), trait_name, trait_path: None, krate: file.krate.clone(), file: file.path.clone(), methods});
+			},
+			Item::Mod(item) if cfg.is_enabled(&item.attrs) => {
+				if let Some((_, items)) = &item.content {
+					module.push(item.ident.to_string());
+					collect_items(file, module, items, cfg, index);
+					module.pop();
+				}
+			},
+			_ => {},
+		}
+	}
+	index.scopes.push(module_scope);
+}
+
+/// Collects the data types of an item and of the blocks in it, the types in a block are under the path of the function
+/// the block is in Eg. "app::routes::index::Payload", the same path its other items get when the function is visited
+struct TypeCollector<'a> {
+	file: &'a ModuleFile,
+	cfg: &'a Cfg,
+	/// The canonical path of the module or the function the items are in
+	prefix: String,
+	types: &'a mut Vec<TypeDefinition>,
+}
+
+impl TypeCollector<'_> {
+	/// Collect the types of a body under the path of the function it belongs to
+	fn collect_body(&mut self, prefix: String, collect: impl FnOnce(&mut Self)) {
+		let previous = std::mem::replace(&mut self.prefix, prefix);
+		collect(self);
+		self.prefix = previous;
+	}
+}
+
+impl<'ast> Visit<'ast> for TypeCollector<'_> {
+	fn visit_stmt(&mut self, stmt: &'ast syn::Stmt) {
+		if self.cfg.is_enabled(utils::get_stmt_attrs(stmt)) {
+			visit::visit_stmt(self, stmt);
+		}
+	}
+
+	/// Functions, methods, constants and statics have bodies of their own, inline modules are collected by the index
+	fn visit_item(&mut self, item: &'ast Item) {
+		if !self.cfg.is_enabled(utils::get_item_attrs(item)) {
+			return
+		}
+
+		match item {
+			Item::Struct(_) | Item::Enum(_) | Item::Union(_) | Item::Type(_) => {
+				self.types.extend(types::type_definition(item, &self.file.krate, &self.prefix, &self.file.path));
+			},
+			Item::Fn(item) => self.collect_body(format!("{}::{}", self.prefix, item.sig.ident), |collector| collector.visit_block(&item.block)),
+			Item::Impl(item) => {
+				let (self_type, _) = utils::get_impl_names(&self.prefix, item);
+				let self_type = analysis::crate_path(&self_type, &self.file.krate);
+				for method in item.items.iter().filter_map(|item| match item {
+					ImplItem::Fn(method) if self.cfg.is_enabled(&method.attrs) => Some(method),
+					_ => None,
+				}) {
+					self.collect_body(format!("{}::{}", self_type, method.sig.ident), |collector| collector.visit_block(&method.block));
+				}
+			},
+			Item::Trait(item) => {
+				let path = format!("{}::{}", self.prefix, item.ident);
+				for (method, block) in item.items.iter().filter_map(|item| match item {
+					TraitItem::Fn(method) if self.cfg.is_enabled(&method.attrs) => method.default.as_ref().map(|block| (method, block)),
+					_ => None,
+				}) {
+					self.collect_body(format!("{}::{}", path, method.sig.ident), |collector| collector.visit_block(block));
+				}
+			},
+			Item::Const(item) => self.collect_body(format!("{}::{}", self.prefix, item.ident), |collector| collector.visit_expr(&item.expr)),
+			Item::Static(item) => self.collect_body(format!("{}::{}", self.prefix, item.ident), |collector| collector.visit_expr(&item.expr)),
+			_ => {},
+		}
+	}
+}
@@ -1,2 +1,4 @@
+pub mod project;
+pub mod source;
 pub mod toml;
-pub mod tree;
\ No newline at end of file
+pub mod tree;
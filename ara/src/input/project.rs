@@ -0,0 +1,74 @@
+/*-------------
+/project.rs
+
+This file is for reading the configuration that is checked into the project. It is read from an `ara.toml` at the root
+of the project, or from `[workspace.metadata.ara]` / `[package.metadata.ara]` in the Cargo.toml.
+-------------*/
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use crate::config::{Format, Framework};
+use crate::error::{Error, Result, Span};
+use crate::input::source::Source;
+
+/// The configuration of the project, everything that isn't set falls back to the defaults or command line arguments
+/// Keys that aren't known are an error so a mistyped key isn't silently ignored.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub ignore: Option<Vec<String>>,
+    pub include: Option<Vec<String>>,
+    pub gitignore: Option<bool>,
+    pub cache: Option<bool>,
+    pub function_name: Option<Vec<String>>,
+    pub features: Option<Vec<String>>,
+    pub all_features: Option<bool>,
+    pub no_default_features: Option<bool>,
+    pub release: Option<bool>,
+    pub cfg: Option<Vec<String>>,
+    pub framework: Option<Framework>,
+    pub formats: Option<Vec<Format>>,
+    /// The file each format is written to Eg. `json = "target/ara.json"`
+    pub output: BTreeMap<Format, String>,
+}
+
+impl ProjectConfig {
+    /// Load the configuration of the project, an empty configuration is returned when the project has none
+    pub fn load(source: &dyn Source) -> Result<ProjectConfig> {
+        match source.read("ara.toml") {
+            Ok(contents) => return parse("ara.toml", &contents),
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(Error::io("ara.toml", err)),
+            Err(_) => {}
+        }
+
+        // A missing or broken Cargo.toml is reported when the workspace is read
+        let contents = match source.read("Cargo.toml") {
+            Ok(contents) => contents,
+            Err(_) => return Ok(ProjectConfig::default()),
+        };
+        let manifest: toml::Table = match toml::from_str(&contents) {
+            Ok(manifest) => manifest,
+            Err(_) => return Ok(ProjectConfig::default()),
+        };
+
+        let metadata = ["workspace", "package"].iter()
+            .find_map(|table| manifest.get(*table)?.get("metadata")?.get("ara"));
+        match metadata {
+            Some(metadata) => metadata.clone().try_into().map_err(|err: toml::de::Error| Error::ConfigParse {
+                path: "Cargo.toml".to_string(),
+                message: err.message().to_string(),
+                span: None,
+            }),
+            None => Ok(ProjectConfig::default()),
+        }
+    }
+}
+
+/// Parse the configuration file
+fn parse(path: &str, contents: &str) -> Result<ProjectConfig> {
+    toml::from_str(contents).map_err(|err| Error::ConfigParse {
+        path: path.to_string(),
+        message: err.message().to_string(),
+        span: err.span().map(|span| Span::from_offset(contents, span.start)),
+    })
+}
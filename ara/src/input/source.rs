@@ -0,0 +1,143 @@
+/*-------------
+/source.rs
+
+This file is for the sources the project can be read from. All paths are relative to the root of the project so the
+same analysis can run on the disk or on a set of files that only exist in memory.
+-------------*/
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+/// An entry of a directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: String,
+    pub is_dir: bool,
+}
+
+/// Where the files of the project are read from
+pub trait Source: Send + Sync {
+    /// Read the file at the path
+    fn read(&self, path: &str) -> io::Result<String>;
+    /// List the entries of the directory at the path, the root of the project is the empty path
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>>;
+    /// Check if there is a file at the path
+    fn exists(&self, path: &str) -> bool {
+        self.read(path).is_ok()
+    }
+    /// The root of the project that the paths are relative to, errors name their files with it
+    fn root(&self) -> &str {
+        ""
+    }
+}
+
+/// Join a file name onto a directory path
+pub fn join(directory: &str, name: &str) -> String {
+    if directory.is_empty() || directory == "." {
+        name.to_string()
+    } else {
+        format!("{}/{}", directory.trim_end_matches('/'), name)
+    }
+}
+
+/// Reads the project from the disk
+#[derive(Debug, Clone)]
+pub struct DiskSource {
+    root: String,
+}
+
+impl DiskSource {
+    pub fn new(root: &str) -> DiskSource {
+        DiskSource { root: root.to_string() }
+    }
+    fn full_path(&self, path: &str) -> String {
+        if path.is_empty() {
+            self.root.clone()
+        } else {
+            join(&self.root, path)
+        }
+    }
+}
+
+impl Source for DiskSource {
+    fn read(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(self.full_path(path))
+    }
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(self.full_path(path))? {
+            let entry = entry?;
+            entries.push(Entry {
+                path: join(path, &entry.file_name().to_string_lossy()),
+                is_dir: entry.path().is_dir(),
+            });
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+    fn exists(&self, path: &str) -> bool {
+        Path::new(&self.full_path(path)).is_file()
+    }
+    fn root(&self) -> &str {
+        &self.root
+    }
+}
+
+/// Reads the project from files that only exist in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    files: BTreeMap<String, String>,
+}
+
+impl MemorySource {
+    /// Create a source with the Cargo.toml of the project
+    pub fn new(manifest: &str) -> MemorySource {
+        let mut source = MemorySource::default();
+        source.add_file("Cargo.toml", manifest);
+        source
+    }
+    /// Create a source from the Cargo.toml and a map of paths to the source of the files
+    pub fn from_files(manifest: &str, files: BTreeMap<String, String>) -> MemorySource {
+        let mut source = MemorySource::new(manifest);
+        source.files.extend(files);
+        source
+    }
+    /// Add a file to the source
+    pub fn add_file(&mut self, path: &str, content: &str) -> &mut MemorySource {
+        self.files.insert(path.trim_start_matches("./").to_string(), content.to_string());
+        self
+    }
+}
+
+impl Source for MemorySource {
+    fn read(&self, path: &str) -> io::Result<String> {
+        match self.files.get(path) {
+            Some(content) => Ok(content.clone()),
+            None => Err(io::Error::new(ErrorKind::NotFound, format!("no file `{}`", path))),
+        }
+    }
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>> {
+        let prefix = if path.is_empty() || path == "." { String::new() } else { format!("{}/", path.trim_end_matches('/')) };
+
+        let mut entries: Vec<Entry> = Vec::new();
+        for file in self.files.keys().filter(|file| file.starts_with(&prefix)) {
+            let rest = &file[prefix.len()..];
+            let entry = match rest.split_once('/') {
+                Some((directory, _)) => Entry { path: join(path, directory), is_dir: true },
+                None => Entry { path: join(path, rest), is_dir: false },
+            };
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+
+        if entries.is_empty() && !prefix.is_empty() {
+            return Err(io::Error::new(ErrorKind::NotFound, format!("no directory `{}`", path)));
+        }
+        Ok(entries)
+    }
+}
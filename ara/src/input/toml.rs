@@ -1,54 +1,321 @@
 /*-------------
 /toml.rs
 
-This file is for parsing the toml file and returning the data about the workspace.
+This file is for parsing the toml files and returning the data about the workspace. The root Cargo.toml gives the
+members of the workspace, member globs are expanded and every package manifest is read for its targets and path
+dependencies.
 -------------*/
-use serde_derive::Deserialize;
-use tracing::{error, warn};
-use std::fs;
-use std::process::exit;
+use globset::Glob;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use toml;
-use crate::config::APP;
+use crate::input::source::{join, Source};
+use crate::error::{Error, Result, Span};
+use crate::module::normalize;
 
-// Top level struct to hold the TOML data.
-#[derive(Deserialize, Debug)]
+/// The data about the workspace and its packages
+#[derive(Debug, Clone, Default)]
 pub struct Data {
-   pub workspace: Workspace,
+    pub workspace: Workspace,
+    pub packages: Vec<Package>,
 }
 
-// Config struct holds to data from the `[config]` section.
-#[derive(Deserialize, Debug)]
+/// The members of the workspace, globs are already expanded
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 pub struct Workspace {
     pub members: Vec<String>,
+    pub exclude: Vec<String>,
+    pub dependencies: BTreeMap<String, DependencyTable>,
 }
 
-/// This parses the toml file and returns data about what the workspace is/isn't.
-pub fn parse_toml() -> Data {
-    let filename = format!("{}/Cargo.toml", APP.path);
+/// A package of the workspace
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Package {
+    /// Name of the package Eg. "my-app"
+    pub name: String,
+    /// Name of the package as a crate Eg. "my_app"
+    pub crate_name: String,
+    /// Directory of the package relative to the project
+    pub path: String,
+    pub targets: Vec<Target>,
+    /// The dependencies of the package that are found at a path
+    pub dependencies: Vec<Dependency>,
+    /// The features of the package and what they enable, optional dependencies are features of their own
+    pub features: BTreeMap<String, Vec<String>>,
+}
+
+/// A library or binary target of a package
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Target {
+    pub name: String,
+    pub crate_name: String,
+    pub kind: TargetKind,
+    /// The crate root of the target relative to the project
+    pub path: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    Lib,
+    Bin,
+}
+
+/// A dependency of a package that is found at a path
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Dependency {
+    /// Name of the dependency in the manifest Eg. "shared-types"
+    pub name: String,
+    /// Name the dependency is used with in the code Eg. "shared_types"
+    pub crate_name: String,
+    /// Name of the package of the dependency
+    pub package: String,
+    /// Directory of the dependency relative to the project
+    pub path: String,
+    /// The dependency is only used when a feature enables it
+    pub optional: bool,
+    /// The features of the dependency that are enabled
+    pub features: Vec<String>,
+    /// The default feature of the dependency is enabled
+    pub default_features: bool,
+}
+
+// The tables of a Cargo.toml that are read
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct Manifest {
+    workspace: Option<Workspace>,
+    package: Option<PackageTable>,
+    lib: Option<TargetTable>,
+    bin: Vec<TargetTable>,
+    dependencies: BTreeMap<String, DependencyTable>,
+    features: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PackageTable {
+    name: String,
+    autobins: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct TargetTable {
+    name: Option<String>,
+    path: Option<String>,
+}
+
+/// A dependency is either a version or a table
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum DependencyTable {
+    Version(String),
+    Detailed {
+        path: Option<String>,
+        package: Option<String>,
+        workspace: Option<bool>,
+        optional: Option<bool>,
+        features: Option<Vec<String>>,
+        #[serde(rename = "default-features")]
+        default_features: Option<bool>,
+    },
+}
+
+/// This parses the toml files and returns data about what the workspace is/isn't.
+/// A single crate without a `[workspace]` is its own only member, members whose manifest can't be read are added to
+/// the diagnostics.
+pub fn parse_toml(source: &dyn Source, diagnostics: &mut Vec<Error>) -> Result<Data> {
+    let filename = "Cargo.toml".to_string();
 
-    let contents = match fs::read_to_string(&filename) {
+    let contents = match source.read(&filename) {
         Ok(c) => c,
-        Err(_) => {
-            error!("Could not read file `{}`", filename);
-            exit(1);
+        Err(_) => return Err(Error::ManifestNotFound { path: join(source.root(), &filename) }),
+    };
+    let manifest = read_manifest(&join(source.root(), &filename), &contents)?;
+
+    let mut data = Data::default();
+    let root = match (&manifest.workspace, &manifest.package) {
+        (Some(workspace), _) => workspace.clone(),
+        (None, Some(_)) => Workspace {members: vec![".".to_string()], ..Workspace::default()},
+        (None, None) => return Err(Error::NoPackageOrWorkspace { path: join(source.root(), &filename) }),
+    };
+    let is_workspace = manifest.workspace.is_some();
+    if let Some(package) = load_package(source, "", manifest, &root) {
+        data.packages.push(package);
+    }
+
+    if !is_workspace {
+        data.workspace = root;
+        return Ok(data);
+    }
+
+    let members = expand_members(source, &root.members, &root.exclude);
+    for member in &members {
+        let filename = join(member, "Cargo.toml");
+        let contents = match source.read(&filename) {
+            Ok(contents) => contents,
+            Err(_) => {
+                diagnostics.push(Error::ManifestNotFound { path: join(source.root(), &filename) });
+                continue;
+            }
+        };
+        match read_manifest(&join(source.root(), &filename), &contents) {
+            Ok(manifest) => data.packages.extend(load_package(source, member, manifest, &root)),
+            Err(err) => diagnostics.push(err),
         }
+    }
+
+    data.workspace = Workspace {members, ..root};
+    Ok(data)
+}
+
+/// Get the name of a crate from the name of a package Eg. "my-app" is "my_app"
+pub fn crate_name(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Deserialize a manifest, the path is the one errors name it by
+fn read_manifest(filename: &str, contents: &str) -> Result<Manifest> {
+    toml::from_str(contents).map_err(|err| Error::ManifestParse {
+        span: err.span().map(|span| Span::from_offset(contents, span.start)),
+        message: err.message().to_string(),
+        path: filename.to_string(),
+    })
+}
+
+/// Expand the globs of the members, only directories with a Cargo.toml are members of a glob
+fn expand_members(source: &dyn Source, patterns: &[String], exclude: &[String]) -> Vec<String> {
+    let mut members: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let pattern = normalize(pattern);
+        let expanded = if pattern.contains(['*', '?', '[']) {
+            expand_glob(source, "", &pattern.split('/').collect::<Vec<_>>())
+                .into_iter()
+                .filter(|member| source.exists(&join(member, "Cargo.toml")))
+                .collect()
+        } else {
+            vec![pattern]
+        };
+
+        for member in expanded {
+            let excluded = exclude.iter().map(|exclude| normalize(exclude)).any(|exclude| member == exclude || member.starts_with(&format!("{}/", exclude)));
+            if !excluded && !members.contains(&member) {
+                members.push(member);
+            }
+        }
+    }
+    members
+}
+
+/// Match the directories of the project against the components of a glob
+fn expand_glob(source: &dyn Source, directory: &str, components: &[&str]) -> Vec<String> {
+    let (component, rest) = match components.split_first() {
+        Some(split) => split,
+        None => return vec![directory.to_string()],
+    };
+
+    let matcher = match Glob::new(component) {
+        Ok(glob) => glob.compile_matcher(),
+        Err(_) => return Vec::new(),
     };
+    let mut matches = Vec::new();
+    for entry in source.read_dir(directory).unwrap_or_default() {
+        let name = entry.path.rsplit('/').next().unwrap_or_default();
+        if entry.is_dir && matcher.is_match(name) {
+            matches.extend(expand_glob(source, &entry.path, rest));
+        }
+    }
+    matches
+}
+
+/// Create the package of a manifest with its targets and path dependencies, None is returned for virtual manifests
+fn load_package(source: &dyn Source, directory: &str, manifest: Manifest, workspace: &Workspace) -> Option<Package> {
+    let table = manifest.package?;
+    let mut targets = Vec::new();
 
-    if !contents.contains("workspace") {
-        warn!("No workspace found in `{}`", filename);
-        return Data {
-            workspace: Workspace {
-                members: vec!["axum".to_string()],
+    // The library is `src/lib.rs` unless the `[lib]` table says otherwise
+    let lib_path = manifest.lib.as_ref().and_then(|lib| lib.path.clone()).unwrap_or("src/lib.rs".to_string());
+    if manifest.lib.is_some() || source.exists(&join(directory, &lib_path)) {
+        let name = manifest.lib.as_ref().and_then(|lib| lib.name.clone()).unwrap_or(table.name.clone());
+        targets.push(Target {crate_name: crate_name(&name), name, kind: TargetKind::Lib, path: normalize(&join(directory, &lib_path))});
+    }
+
+    // Binaries that are declared in the manifest
+    for bin in &manifest.bin {
+        let name = bin.name.clone().unwrap_or(table.name.clone());
+        let path = match &bin.path {
+            Some(path) => path.clone(),
+            None => {
+                let candidates = [format!("src/bin/{}.rs", name), format!("src/bin/{}/main.rs", name), "src/main.rs".to_string()];
+                candidates.iter().find(|path| source.exists(&join(directory, path))).cloned().unwrap_or(candidates[0].clone())
             },
         };
+        targets.push(Target {crate_name: crate_name(&name), name, kind: TargetKind::Bin, path: normalize(&join(directory, &path))});
     }
 
-    let data: Data = match toml::from_str(&contents) {
-        Ok(d) => d,
-        Err(_) => {
-            error!("Unable to load data from `{}`", filename);
-            exit(1);
+    // Binaries that are found in `src/main.rs` and `src/bin`
+    if table.autobins.unwrap_or(true) {
+        let mut found = Vec::new();
+        if source.exists(&join(directory, "src/main.rs")) {
+            found.push((table.name.clone(), join(directory, "src/main.rs")));
         }
-    };
-    data
-}
\ No newline at end of file
+        for entry in source.read_dir(&join(directory, "src/bin")).unwrap_or_default() {
+            let name = entry.path.rsplit('/').next().unwrap_or_default().trim_end_matches(".rs").to_string();
+            if entry.is_dir && source.exists(&join(&entry.path, "main.rs")) {
+                found.push((name, join(&entry.path, "main.rs")));
+            } else if !entry.is_dir && entry.path.ends_with(".rs") {
+                found.push((name, entry.path));
+            }
+        }
+
+        for (name, path) in found {
+            if !targets.iter().any(|target| target.kind == TargetKind::Bin && (target.name == name || target.path == path)) {
+                targets.push(Target {crate_name: crate_name(&name), name, kind: TargetKind::Bin, path});
+            }
+        }
+    }
+
+    let mut dependencies = Vec::new();
+    let mut features = manifest.features;
+    for (name, table) in &manifest.dependencies {
+        let (optional, mut enabled) = match table {
+            DependencyTable::Detailed { optional, features, .. } => (optional.unwrap_or(false), features.clone().unwrap_or_default()),
+            DependencyTable::Version(_) => (false, Vec::new()),
+        };
+
+        // Optional dependencies are features of their own unless a feature enables them with `dep:`
+        let explicit = format!("dep:{}", name);
+        if optional && !features.values().flatten().any(|value| *value == explicit) {
+            features.entry(name.clone()).or_insert(vec![explicit]);
+        }
+
+        // Dependencies inherited from the workspace are relative to the root of the project, their features add up
+        let (dependency, directory) = match table {
+            DependencyTable::Detailed { workspace: Some(true), .. } => match workspace.dependencies.get(name) {
+                Some(dependency) => (dependency, ""),
+                None => continue,
+            },
+            dependency => (dependency, directory),
+        };
+
+        if let DependencyTable::Detailed { path: Some(path), package, features, default_features, .. } = dependency {
+            for feature in features.iter().flatten() {
+                if !enabled.contains(feature) {
+                    enabled.push(feature.clone());
+                }
+            }
+            dependencies.push(Dependency {
+                name: name.clone(),
+                crate_name: crate_name(name),
+                package: package.clone().unwrap_or(name.clone()),
+                path: normalize(&join(directory, path)),
+                optional,
+                features: enabled,
+                default_features: default_features.unwrap_or(true),
+            });
+        }
+    }
+
+    Some(Package {crate_name: crate_name(&table.name), name: table.name, path: directory.to_string(), targets, dependencies, features})
+}
@@ -26,7 +26,7 @@ impl BTree {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Node {
     keys: Vec<String>,
     children: Vec<Node>,
@@ -45,8 +45,8 @@ impl Node {
     pub fn add_child(&mut self, child: Node) {
         self.children.push(child);
     }
-    pub fn to_string(&self) -> String {
-        self.keys.clone()[0].clone()
+    pub fn get_key(&self) -> String {
+        self.keys[0].clone()
     }
     pub fn collect_leafs(&self, leafs: &mut Vec<String>) {
         leafs.extend(self.keys.clone());
@@ -1,66 +1,97 @@
 /*-------------
 /lib.rs
 
-This file is for the connection to the server and tokens.
+This file is the entry point of the library. It grabs the files of the project, reads the workspace and hands the
+crate roots to the engine which visits every file to build the graph.
 -------------*/
 #[macro_use] extern crate quote;
-#[macro_use] extern crate lazy_static;
-use state::State;
-use tracing::error;
-use std::error::Error;
-use std::fs;
-use syn::visit::Visit;
-use input::{toml, tree::{BTree, Node}};
-use config::APP;
+use std::collections::{BTreeMap, BTreeSet};
+use cache::Cache;
+use clap::Parser;
+use input::{source::{DiskSource, Source}, toml, tree::{BTree, Node}};
 
+pub use analysis::{Analysis, Changes, Function};
+pub use config::{Args, Command, Format, Framework, Options, Output};
+pub use error::{Error, Result};
+pub use input::source::MemorySource;
+
+pub mod analysis;
+pub mod cache;
+pub mod cfg;
 pub mod config;
+pub mod engine;
+pub mod error;
 pub mod file;
 pub mod graph;
+pub mod index;
 pub mod input;
 pub mod log;
+pub mod module;
+pub mod output;
 pub mod parse;
-pub mod stack;
+pub mod scope;
 pub mod state;
+pub mod types;
+
+
+/// Launch the application with the options parsed from the command line arguments and the project configuration
+pub fn launch() -> Result<Analysis> {
+	analyze(Options::from_args(Args::parse())?)
+}
+
+/// Analyze the project on the disk at the path of the options, unchanged files are loaded from the cache when it's on
+pub fn analyze(options: Options) -> Result<Analysis> {
+    let source = DiskSource::new(&options.path);
+    let cache = Cache::from_options(&options);
+    let analysis = run(options, &source, cache.as_ref())?;
+    if let Some(cache) = cache {
+        cache.prune();
+    }
+    Ok(analysis)
+}
 
+/// Analyze a project that only exists in memory, the files are relative to the root of the project
+pub fn analyze_memory(options: Options, manifest: &str, files: BTreeMap<String, String>) -> Result<Analysis> {
+    let source = MemorySource::from_files(manifest, files);
+    analyze_source(options, &source)
+}
+
+/// Analyze the project that is read from the source, the cache isn't used since the source might not be on the disk
+pub fn analyze_source(options: Options, source: &dyn Source) -> Result<Analysis> {
+    run(options, source, None)
+}
+
+/// Run the analysis of the project
+/// Only a missing or broken manifest stops the analysis, errors in single files are added to the diagnostics.
+fn run(options: Options, source: &dyn Source, cache: Option<&Cache>) -> Result<Analysis> {
+    let mut analysis = Analysis::default();
 
-/// Launch the application
-/// This need a bit of a rework to make it more modular but im not done with the application yet so
-/// this will do for now.
-pub fn launch() -> Result<(), Box<dyn Error>> {
 	// Grab Directory and files
     let mut root = Node::new();
-    root.add_key(&APP.path);
+    root.add_key("");
 
     let mut directories = BTree::new(Some(root));
-    file::grab_files(&mut directories.get_root().as_mut().unwrap());
-
-   //log::debug::debug(&directories);
+    if let Some(root) = directories.get_root().as_mut() {
+        let filter = file::Filter::new(&options)?;
+        file::grab_files(root, source, &filter, &mut Vec::new(), &mut analysis);
+    }
 
     // Grab Workspace
-    let workspace = toml::parse_toml();
+    let workspace = toml::parse_toml(source, &mut analysis.diagnostics)?;
+    let targets = workspace.packages.iter().flat_map(|package| package.targets.iter());
 
-    // Create a new Graph
-    let mut visitor = State::new();
-    workspace.workspace.members.iter().for_each(|member| {
-        visitor.add_workspace_lib(member.clone());
-    });
+    // Start at the crate roots of every target and follow the module declarations, files that were ignored aren't parsed
+    let roots = targets.map(module::crate_root).collect();
+    let files: BTreeSet<String> = directories.get_all_leafs().into_iter().filter(|leaf| leaf.ends_with(".rs")).collect();
+    analysis.diagnostics.extend(cfg::check_options(&options));
+    let cfgs = cfg::crate_cfgs(&workspace.packages, &options);
+    let context = engine::Context {source, options: &options, cache, cfgs: &cfgs};
+    engine::analyze_modules(&context, roots, &files, &mut analysis);
+    analysis.members = workspace.workspace.members;
+    analysis.packages = workspace.packages;
 
-    for leaf in directories.get_all_leafs() {
-        if leaf.ends_with(".rs") {
-            let content = fs::read_to_string(leaf.clone())?;
-            let syntax = match syn::parse_file(&content) {
-                Ok(syntax) => syntax,
-                Err(err) => {
-                    error!("Error parsing file: {}", leaf);
-                    error!("{}", err);
-                    continue;
-                }
-            };
-            visitor.update_current_file(leaf);
-            visitor.visit_file(&syntax);
-            visitor.clear_libs();
-        }
-    }
-	
-	Ok(())
-}
\ No newline at end of file
+    // Files that aren't part of the module tree of any crate are skipped
+    let unused: Vec<_> = files.into_iter().filter(|file| !analysis.parsed.contains(file) && !analysis.skipped.contains(file)).collect();
+    analysis.skipped.extend(unused);
+	Ok(analysis)
+}
@@ -5,46 +5,50 @@ This file is for the logging of the application and currently uses the colored c
 and its own methods for printing out the information. In the future this MAY be deprecated for tracing.
 -------------*/
 use colored::Colorize;
-use crate::config::APP;
+use std::fmt::{Debug, Display};
+use crate::config::Options;
 
-pub mod info {
-	use super::*;
+/// Logger that carries the verbosity of the current analysis
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Log {
+	pub verbose: bool,
+}
+
+impl Log {
+	pub fn new(options: &Options) -> Log {
+		Log {verbose: options.verbose}
+	}
 
-	pub fn print_visit_function(function: &str, file: &str) {
+	pub fn print_visit_function(&self, function: &str, file: &str) {
 		println!("{} {} {}{}", "Visiting Function:".blue().bold(), "fn".green(), function.green(), "()".green());
 		println!("\t{} {}", "in".italic(), &file.italic());
 	}
-	
-	pub fn print_loc(loc: &str) {
-		if !APP.verbose {
+
+	pub fn print_loc(&self, loc: &str) {
+		if !self.verbose {
 			println!("LOC: {}", loc.green());
 		}
 	}
-}
-
-pub mod debug {
-	use super::*;
-	use std::fmt::Display;
 
-	pub fn debug<T: std::fmt::Debug + Display>(msg: &T) {
-		if APP.verbose {
+	pub fn debug<T: Debug + Display>(&self, msg: &T) {
+		if self.verbose {
 			println!("{} {}", "Debug:".blue().bold(), msg);
 		}
 	}
 
-	pub fn print_expr(loc: &str) {
-		if APP.verbose {
+	pub fn print_expr(&self, loc: &str) {
+		if self.verbose {
 			println!("LOC: {}", loc.green());
 		}
 	}
 
-	pub fn warn<T: std::fmt::Debug + Display>(msg: &T) {
-		if APP.verbose {
+	pub fn warn<T: Debug + Display>(&self, msg: &T) {
+		if self.verbose {
 			println!("{} {}", "Warning:".yellow().bold(), msg);
 		}
 	}
 
-	pub fn error<T: std::fmt::Debug + Display>(msg: &T) {
+	pub fn error<T: Debug + Display>(&self, msg: &T) {
 		println!("{} {}", "Error:".red().bold(), msg);
 	}
-}
\ No newline at end of file
+}
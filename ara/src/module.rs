@@ -0,0 +1,179 @@
+/*-------------
+/module.rs
+
+This file is for discovering the source files of the project the way rustc does. Discovery starts at the crate roots
+of every member and follows the `mod` declarations, including `#[path = "..."]` attributes and inline modules, so each
+file gets its real module path Eg. `crate::routes::users`. Declarations whose `#[cfg(...)]` doesn't hold are skipped.
+-------------*/
+use serde_derive::{Deserialize, Serialize};
+use syn::{Attribute, Item};
+use crate::cfg::{self, Cfg};
+use crate::error::{Error, Span};
+use crate::input::source::{join, Source};
+use crate::input::toml::Target;
+
+/// A file of the module tree
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModuleFile {
+	/// Path of the file relative to the project
+	pub path: String,
+	/// Name of the crate the file belongs to
+	pub krate: String,
+	/// Module path of the file Eg. ["crate", "routes", "users"]
+	pub module: Vec<String>,
+	/// Directory the `mod x;` declarations of the file are looked up in
+	pub dir: String,
+	/// The cfg conditions of the modules the file is declared in
+	pub cfg: Vec<String>,
+}
+
+impl ModuleFile {
+	/// Get the module path of the file Eg. "crate::routes::users"
+	pub fn module_path(&self) -> String {
+		self.module.join("::")
+	}
+}
+
+/// Get the crate root of a target, the root is always a mod-rs file
+pub fn crate_root(target: &Target) -> ModuleFile {
+	ModuleFile {
+		path: target.path.clone(),
+		krate: target.crate_name.clone(),
+		module: vec!["crate".to_string()],
+		dir: parent(&target.path).to_string(),
+		cfg: Vec::new(),
+	}
+}
+
+/// A `mod x;` declaration and the files it can be found in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Declaration {
+	/// Module path of the declared module
+	pub module: Vec<String>,
+	/// The files the module can be in with the directory of their children, the first that exists is used
+	pub candidates: Vec<(String, String)>,
+	pub span: Span,
+	/// The cfg conditions of the declaration and the modules around it
+	pub cfg: Vec<String>,
+}
+
+/// The module that declarations are collected in
+struct Scope {
+	module: Vec<String>,
+	/// Where the children of the module live
+	dir: String,
+	inline: bool,
+	cfg: Vec<String>,
+}
+
+/// Get the `mod x;` declarations of the file that are enabled, inline modules are searched as well
+pub fn declared_modules(file: &ModuleFile, syntax: &syn::File, cfg: &Cfg) -> Vec<Declaration> {
+	let mut declarations = Vec::new();
+	if !cfg.is_enabled(&syntax.attrs) {
+		return declarations;
+	}
+
+	let scope = Scope {module: file.module.clone(), dir: file.dir.clone(), inline: false, cfg: [file.cfg.clone(), cfg::conditions(&syntax.attrs)].concat()};
+	collect_modules(file, &scope, &syntax.items, cfg, &mut declarations);
+	declarations
+}
+
+/// Find the files of the declarations, declarations without a file are returned as errors
+pub fn resolve_modules(source: &dyn Source, file: &ModuleFile, declarations: &[Declaration]) -> (Vec<ModuleFile>, Vec<Error>) {
+	let mut found = Vec::new();
+	let mut missing = Vec::new();
+
+	for declaration in declarations {
+		match declaration.candidates.iter().find(|(path, _)| source.exists(path)) {
+			Some((path, dir)) => found.push(ModuleFile {
+				path: path.clone(),
+				krate: file.krate.clone(),
+				module: declaration.module.clone(),
+				dir: dir.clone(),
+				cfg: declaration.cfg.clone(),
+			}),
+			None => missing.push(Error::ModuleNotFound {
+				path: file.path.clone(),
+				module: declaration.module.join("::"),
+				candidates: declaration.candidates.iter().map(|(path, _)| path.clone()).collect(),
+				span: declaration.span,
+			}),
+		}
+	}
+	(found, missing)
+}
+
+/// Walk the items of the scope for module declarations
+fn collect_modules(file: &ModuleFile, scope: &Scope, items: &[Item], cfg: &Cfg, declarations: &mut Vec<Declaration>) {
+	let dir = &scope.dir;
+	for item in items {
+		let item = match item {
+			Item::Mod(item) if cfg.is_enabled(&item.attrs) => item,
+			_ => continue,
+		};
+
+		let name = item.ident.to_string();
+		let mut child = scope.module.clone();
+		child.push(name.clone());
+		let conditions = [scope.cfg.clone(), cfg::conditions(&item.attrs)].concat();
+
+		if let Some((_, items)) = &item.content {
+			// Paths inside inline modules are relative to the inline module as a directory
+			let child_dir = match path_attribute(&item.attrs) {
+				Some(path) => normalize(&join(dir, &path)),
+				None => join(dir, &name),
+			};
+			collect_modules(file, &Scope {module: child, dir: child_dir, inline: true, cfg: conditions}, items, cfg, declarations);
+			continue;
+		}
+
+		let candidates = match path_attribute(&item.attrs) {
+			// Outside of inline modules the path is relative to the directory of the file
+			Some(path) if !scope.inline => vec![normalize(&join(parent(&file.path), &path))],
+			Some(path) => vec![normalize(&join(dir, &path))],
+			None => vec![join(dir, &format!("{}.rs", name)), join(&join(dir, &name), "mod.rs")],
+		};
+		let candidates = candidates.into_iter().map(|path| {
+			// Files from a path attribute own their directory like a mod.rs file
+			let child_dir = match path_attribute(&item.attrs) {
+				Some(_) => parent(&path).to_string(),
+				None => join(dir, &name),
+			};
+			(path, child_dir)
+		}).collect();
+
+		let start = item.ident.span().start();
+		declarations.push(Declaration {module: child, candidates, span: Span::new(start.line, start.column + 1), cfg: conditions});
+	}
+}
+
+/// Get the value of a `#[path = "..."]` attribute
+fn path_attribute(attrs: &[Attribute]) -> Option<String> {
+	attrs.iter()
+		.filter(|attr| attr.path().is_ident("path"))
+		.find_map(|attr| match &attr.meta.require_name_value().ok()?.value {
+			syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => Some(lit.value()),
+			_ => None,
+		})
+}
+
+/// Get the directory of the path
+fn parent(path: &str) -> &str {
+	match path.rfind('/') {
+		Some(index) => &path[..index],
+		None => "",
+	}
+}
+
+/// Remove the `.` and `..` components of the path
+pub fn normalize(path: &str) -> String {
+	let mut components: Vec<&str> = Vec::new();
+	for component in path.split('/') {
+		match component {
+			"" | "." => {},
+			".." => { components.pop(); },
+			component => components.push(component),
+		}
+	}
+	components.join("/")
+}
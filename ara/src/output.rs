@@ -0,0 +1,53 @@
+/*-------------
+/output.rs
+
+This file is for writing the analysis out in the formats that were chosen in the options.
+-------------*/
+use std::fs;
+use std::path::Path;
+use crate::analysis::Analysis;
+use crate::config::{Format, Options, Output};
+use crate::error::{Error, Result};
+
+/// Write the analysis to every output of the options
+pub fn write(analysis: &Analysis, options: &Options) -> Result<()> {
+	for output in &options.outputs {
+		write_output(analysis, output, options)?;
+	}
+	Ok(())
+}
+
+/// Write the analysis in the format of the output to its file, or to the terminal when it has no file
+pub fn write_output(analysis: &Analysis, output: &Output, options: &Options) -> Result<()> {
+	let contents = render(analysis, output.format);
+
+	let path = match &output.path {
+		Some(path) => path,
+		None => {
+			println!("{}", contents);
+			return Ok(())
+		},
+	};
+
+	// Relative outputs are written relative to the project
+	let path = if Path::new(path).is_absolute() { path.clone() } else { format!("{}/{}", options.path, path) };
+	if let Some(parent) = Path::new(&path).parent() {
+		fs::create_dir_all(parent).map_err(|err| Error::io(&path, err))?;
+	}
+	fs::write(&path, contents).map_err(|err| Error::io(&path, err))
+}
+
+/// Render the analysis in the format
+pub fn render(analysis: &Analysis, format: Format) -> String {
+	match format {
+		Format::Terminal => analysis.functions.iter().map(|function| {
+			// Gated functions are annotated with their conditions
+			let cfg: String = function.cfg.iter().map(|condition| format!("#[cfg({})]\n", condition)).collect();
+			// The functions of the workspace it calls
+			let callees = analysis.callees(&function.path());
+			let calls = if callees.is_empty() { String::new() } else { format!("\nCalls: {:?}", callees) };
+			format!("{}Graph: {:#?}{}", cfg, function.graph, calls)
+		}).collect::<Vec<_>>().join("\n"),
+		Format::Json => serde_json::to_string_pretty(analysis).unwrap_or_default(),
+	}
+}
@@ -0,0 +1,505 @@
+/*-------------
+/parse/expand.rs
+
+This file expands the invocations of the `macro_rules!` macros that are defined in the workspace. The rules are matched
+against the tokens of the invocation the same way rustc does it, the fragments are parsed with syn and the transcriber
+is filled in with what they matched. The expansion is handed back to the visitor which visits it like any other code.
+-------------*/
+use std::collections::HashMap;
+use std::fmt;
+use proc_macro2::{Delimiter, Group, Spacing, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::parse::discouraged::Speculative;
+use syn::parse::{ParseStream, Parser};
+use syn::visit::Visit;
+use crate::error::{Error, Span};
+use crate::index::Lookup;
+use crate::state::State;
+
+/// How deep expansions can be nested before the expansion is stopped
+pub const MAX_DEPTH: usize = 32;
+
+/// A `macro_rules!` macro, the first rule that matches is used
+/// The rules are parsed once when the index is built and shared by every thread.
+#[derive(Debug, Clone)]
+pub struct MacroRules {
+	rules: Vec<(Vec<Matcher>, Vec<Transcriber>)>,
+}
+
+/// A token of the rules that isn't a group, the tokens of proc_macro2 can't be shared between threads
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+	Ident(String),
+	Punct(char, Spacing),
+	Literal(String),
+}
+
+/// A part of the pattern of a rule
+#[derive(Debug, Clone)]
+enum Matcher {
+	Token(Token),
+	Group(Delimiter, Vec<Matcher>),
+	/// A metavariable Eg. `$name:expr`
+	Fragment(String, String),
+	/// A repetition Eg. `$($x:expr),*`
+	Repeat(Vec<Matcher>, Option<Token>, char),
+}
+
+/// A part of the body of a rule
+#[derive(Debug, Clone)]
+enum Transcriber {
+	Token(Token),
+	Group(Delimiter, Vec<Transcriber>),
+	Variable(String),
+	/// `$crate`
+	Crate,
+	Repeat(Vec<Transcriber>, Option<Token>),
+}
+
+impl Token {
+	/// Get the token of a token tree, None is returned for groups
+	fn new(token: &TokenTree) -> Option<Token> {
+		match token {
+			TokenTree::Ident(ident) => Some(Token::Ident(ident.to_string())),
+			TokenTree::Punct(punct) => Some(Token::Punct(punct.as_char(), punct.spacing())),
+			TokenTree::Literal(literal) => Some(Token::Literal(literal.to_string())),
+			TokenTree::Group(_) => None,
+		}
+	}
+
+	/// Create the token tree of the token again
+	fn to_token_tree(&self) -> TokenTree {
+		let span = proc_macro2::Span::call_site();
+		match self {
+			Token::Ident(ident) => match ident.strip_prefix("r#") {
+				Some(raw) => proc_macro2::Ident::new_raw(raw, span).into(),
+				None => proc_macro2::Ident::new(ident, span).into(),
+			},
+			Token::Punct(character, spacing) => proc_macro2::Punct::new(*character, *spacing).into(),
+			Token::Literal(literal) => literal.parse::<proc_macro2::Literal>().unwrap_or_else(|_| proc_macro2::Literal::string(literal)).into(),
+		}
+	}
+
+	/// Check if a token tree of the input is the token, punctuation is matched by its character
+	fn matches(&self, token: &TokenTree) -> bool {
+		match (self, token) {
+			(Token::Punct(character, _), TokenTree::Punct(punct)) => *character == punct.as_char(),
+			(Token::Ident(ident), TokenTree::Ident(found)) => found == ident,
+			(Token::Literal(literal), TokenTree::Literal(found)) => found.to_string() == *literal,
+			_ => false,
+		}
+	}
+}
+
+impl fmt::Display for Token {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Token::Ident(text) | Token::Literal(text) => write!(f, "{}", text),
+			Token::Punct(character, _) => write!(f, "{}", character),
+		}
+	}
+}
+
+/// What a metavariable matched, repetitions hold a binding for every time they matched
+#[derive(Clone)]
+enum Binding {
+	Single(TokenStream),
+	Repeated(Vec<Binding>),
+}
+
+type Bindings = HashMap<String, Binding>;
+
+impl MacroRules {
+	/// Parse the rules from the body of the macro Eg. `($x:expr) => { $x + 1 };`
+	pub fn parse(body: TokenStream) -> Result<MacroRules, String> {
+		let mut rules = Vec::new();
+		let mut tokens = body.into_iter().peekable();
+		while let Some(token) = tokens.next() {
+			let pattern = match token {
+				TokenTree::Group(group) => parse_matchers(group.stream())?,
+				token => return Err(format!("expected the pattern of a rule, found `{}`", token)),
+			};
+			match (tokens.next(), tokens.next()) {
+				(Some(TokenTree::Punct(eq)), Some(TokenTree::Punct(gt))) if eq.as_char() == '=' && gt.as_char() == '>' => {},
+				_ => return Err("expected `=>` after the pattern of a rule".to_string()),
+			}
+			let body = match tokens.next() {
+				Some(TokenTree::Group(group)) => parse_transcribers(group.stream()),
+				_ => return Err("expected the body of a rule".to_string()),
+			};
+			rules.push((pattern, body));
+
+			if let Some(TokenTree::Punct(punct)) = tokens.peek() {
+				if punct.as_char() == ';' {
+					tokens.next();
+				}
+			}
+		}
+		Ok(MacroRules {rules})
+	}
+
+	/// Expand the input with the first rule that matches it, `krate` replaces `$crate`
+	pub fn expand(&self, input: TokenStream, krate: &str) -> Result<TokenStream, String> {
+		for (pattern, body) in &self.rules {
+			let mut bindings = Bindings::new();
+			let matched = (|input: ParseStream| match_all(input, pattern, &mut bindings)).parse2(input.clone());
+			if matched.is_ok() {
+				let mut output = TokenStream::new();
+				transcribe(body, &bindings, &[], krate, &mut output)?;
+				return Ok(output);
+			}
+		}
+		Err("no rules expected these tokens".to_string())
+	}
+}
+
+/// Parse the pattern of a rule
+fn parse_matchers(stream: TokenStream) -> Result<Vec<Matcher>, String> {
+	let mut matchers = Vec::new();
+	let mut tokens = stream.into_iter().peekable();
+	while let Some(token) = tokens.next() {
+		let dollar = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '$');
+		match (dollar, tokens.peek().cloned()) {
+			(true, Some(TokenTree::Ident(name))) => {
+				tokens.next();
+				match (tokens.next(), tokens.next()) {
+					(Some(TokenTree::Punct(colon)), Some(TokenTree::Ident(kind))) if colon.as_char() == ':' => matchers.push(Matcher::Fragment(name.unraw().to_string(), kind.to_string())),
+					_ => return Err(format!("expected a fragment specifier after `${}`", name)),
+				}
+			},
+			(true, Some(TokenTree::Group(group))) if group.delimiter() == Delimiter::Parenthesis => {
+				tokens.next();
+				let (separator, operator) = parse_repetition(&mut tokens)?;
+				matchers.push(Matcher::Repeat(parse_matchers(group.stream())?, separator, operator));
+			},
+			_ => match (&token, Token::new(&token)) {
+				(TokenTree::Group(group), _) => matchers.push(Matcher::Group(group.delimiter(), parse_matchers(group.stream())?)),
+				(_, token) => matchers.extend(token.map(Matcher::Token)),
+			},
+		}
+	}
+	Ok(matchers)
+}
+
+/// Parse the separator and the operator after a repetition Eg. `,*`
+fn parse_repetition(tokens: &mut impl Iterator<Item = TokenTree>) -> Result<(Option<Token>, char), String> {
+	let operator = |token: &TokenTree| match token {
+		TokenTree::Punct(punct) if ['*', '+', '?'].contains(&punct.as_char()) => Some(punct.as_char()),
+		_ => None,
+	};
+	let first = tokens.next().ok_or("expected a repetition operator")?;
+	if let Some(operator) = operator(&first) {
+		return Ok((None, operator));
+	}
+	let second = tokens.next().ok_or("expected a repetition operator")?;
+	match (operator(&second), Token::new(&first)) {
+		(Some(operator), Some(separator)) => Ok((Some(separator), operator)),
+		(Some(_), None) => Err(format!("`{}` can't be the separator of a repetition", first)),
+		(None, _) => Err(format!("expected a repetition operator, found `{}`", second)),
+	}
+}
+
+/// Parse the body of a rule
+fn parse_transcribers(stream: TokenStream) -> Vec<Transcriber> {
+	let mut transcribers = Vec::new();
+	let mut tokens = stream.into_iter().peekable();
+	while let Some(token) = tokens.next() {
+		let dollar = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '$');
+		match (dollar, tokens.peek().cloned()) {
+			(true, Some(TokenTree::Ident(name))) => {
+				tokens.next();
+				match name.to_string().as_str() {
+					"crate" => transcribers.push(Transcriber::Crate),
+					_ => transcribers.push(Transcriber::Variable(name.unraw().to_string())),
+				}
+			},
+			(true, Some(TokenTree::Group(group))) if group.delimiter() == Delimiter::Parenthesis => {
+				tokens.next();
+				// A repetition without an operator isn't a repetition, the tokens are kept as they are
+				match parse_repetition(&mut tokens.clone()) {
+					Ok((separator, _)) => {
+						tokens.nth(if separator.is_some() { 1 } else { 0 });
+						transcribers.push(Transcriber::Repeat(parse_transcribers(group.stream()), separator));
+					},
+					Err(_) => {
+						transcribers.extend(Token::new(&token).map(Transcriber::Token));
+						transcribers.push(Transcriber::Group(group.delimiter(), parse_transcribers(group.stream())));
+					},
+				}
+			},
+			_ => match (&token, Token::new(&token)) {
+				(TokenTree::Group(group), _) => transcribers.push(Transcriber::Group(group.delimiter(), parse_transcribers(group.stream()))),
+				(_, token) => transcribers.extend(token.map(Transcriber::Token)),
+			},
+		}
+	}
+	transcribers
+}
+
+/// Match the whole input against the pattern
+fn match_all(input: ParseStream, matchers: &[Matcher], bindings: &mut Bindings) -> syn::Result<()> {
+	match_sequence(input, matchers, bindings)?;
+	if !input.is_empty() {
+		return Err(input.error("unexpected tokens"));
+	}
+	Ok(())
+}
+
+/// Match the start of the input against the pattern, repetitions match as many times as they can
+fn match_sequence(input: ParseStream, matchers: &[Matcher], bindings: &mut Bindings) -> syn::Result<()> {
+	for matcher in matchers {
+		match matcher {
+			Matcher::Token(expected) => match_token(input, expected)?,
+			Matcher::Group(delimiter, inner) => {
+				let group = match input.parse::<TokenTree>()? {
+					TokenTree::Group(group) if group.delimiter() == *delimiter => group,
+					token => return Err(syn::Error::new(token.span(), "expected a group")),
+				};
+				(|input: ParseStream| match_all(input, inner, bindings)).parse2(group.stream())?;
+			},
+			Matcher::Fragment(name, kind) => {
+				let tokens = parse_fragment(input, kind)?;
+				bindings.insert(name.clone(), Binding::Single(tokens));
+			},
+			Matcher::Repeat(inner, separator, operator) => {
+				let mut matches: Vec<Bindings> = Vec::new();
+				loop {
+					let fork = input.fork();
+					if let (false, Some(separator)) = (matches.is_empty(), separator) {
+						if match_token(&fork, separator).is_err() {
+							break;
+						}
+					}
+					let mut found = Bindings::new();
+					if match_sequence(&fork, inner, &mut found).is_err() || fork.cursor() == input.cursor() {
+						break;
+					}
+					input.advance_to(&fork);
+					matches.push(found);
+					if *operator == '?' {
+						break;
+					}
+				}
+				if *operator == '+' && matches.is_empty() {
+					return Err(input.error("expected at least one repetition"));
+				}
+
+				let mut names = Vec::new();
+				variables(inner, &mut names);
+				for name in names {
+					let repeated = matches.iter().filter_map(|found| found.get(&name).cloned()).collect();
+					bindings.insert(name, Binding::Repeated(repeated));
+				}
+			},
+		}
+	}
+	Ok(())
+}
+
+/// Match a single token
+fn match_token(input: ParseStream, expected: &Token) -> syn::Result<()> {
+	input.step(|cursor| {
+		if let Some((found, next)) = cursor.token_tree() {
+			if expected.matches(&found) {
+				return Ok(((), next));
+			}
+		}
+		Err(cursor.error(format!("expected `{}`", expected)))
+	})
+}
+
+/// Parse a fragment of the kind, expressions and types are wrapped in an invisible group to keep their precedence
+fn parse_fragment(input: ParseStream, kind: &str) -> syn::Result<TokenStream> {
+	let invisible = |tokens: TokenStream| TokenTree::Group(Group::new(Delimiter::None, tokens)).into_token_stream();
+	Ok(match kind {
+		"ident" => input.call(syn::Ident::parse_any)?.into_token_stream(),
+		"lifetime" => input.parse::<syn::Lifetime>()?.into_token_stream(),
+		"literal" => {
+			let minus = input.parse::<Option<syn::Token![-]>>()?;
+			let literal = input.parse::<syn::Lit>()?;
+			quote::quote! { #minus #literal }
+		},
+		"tt" => input.parse::<TokenTree>()?.into_token_stream(),
+		"expr" | "expr_2021" => invisible(input.parse::<syn::Expr>()?.into_token_stream()),
+		"ty" => invisible(input.parse::<syn::Type>()?.into_token_stream()),
+		"path" => input.parse::<syn::Path>()?.into_token_stream(),
+		"pat" => syn::Pat::parse_multi_with_leading_vert(input)?.into_token_stream(),
+		"pat_param" => syn::Pat::parse_single(input)?.into_token_stream(),
+		"stmt" => input.parse::<syn::Stmt>()?.into_token_stream(),
+		"block" => input.parse::<syn::Block>()?.into_token_stream(),
+		"item" => input.parse::<syn::Item>()?.into_token_stream(),
+		"meta" => input.parse::<syn::Meta>()?.into_token_stream(),
+		"vis" => input.parse::<syn::Visibility>()?.into_token_stream(),
+		kind => return Err(input.error(format!("unknown fragment specifier `{}`", kind))),
+	})
+}
+
+/// Get the names of the metavariables of the pattern
+fn variables(matchers: &[Matcher], names: &mut Vec<String>) {
+	for matcher in matchers {
+		match matcher {
+			Matcher::Fragment(name, _) => names.push(name.clone()),
+			Matcher::Group(_, inner) | Matcher::Repeat(inner, _, _) => variables(inner, names),
+			Matcher::Token(_) => {},
+		}
+	}
+}
+
+/// Get the binding of a metavariable at the index of every repetition it is in
+fn lookup<'a>(bindings: &'a Bindings, name: &str, indexes: &[usize]) -> Option<&'a Binding> {
+	let mut binding = bindings.get(name)?;
+	for index in indexes {
+		if let Binding::Repeated(repeated) = binding {
+			binding = repeated.get(*index)?;
+		}
+	}
+	Some(binding)
+}
+
+/// Get how many times a repetition of the body repeats, every metavariable that repeats in it has to agree
+fn repetitions(body: &[Transcriber], bindings: &Bindings, indexes: &[usize]) -> Result<usize, String> {
+	let mut count: Option<usize> = None;
+	let mut stack: Vec<&Transcriber> = body.iter().collect();
+	while let Some(transcriber) = stack.pop() {
+		match transcriber {
+			Transcriber::Variable(name) => {
+				if let Some(Binding::Repeated(repeated)) = lookup(bindings, name, indexes) {
+					match count {
+						Some(count) if count != repeated.len() => return Err(format!("metavariable `{}` repeats {} times, but another repeats {} times", name, repeated.len(), count)),
+						_ => count = Some(repeated.len()),
+					}
+				}
+			},
+			Transcriber::Group(_, inner) | Transcriber::Repeat(inner, _) => stack.extend(inner),
+			_ => {},
+		}
+	}
+	count.ok_or("a repetition doesn't contain a metavariable that repeats".to_string())
+}
+
+/// Fill in the body with the bindings
+fn transcribe(body: &[Transcriber], bindings: &Bindings, indexes: &[usize], krate: &str, output: &mut TokenStream) -> Result<(), String> {
+	for transcriber in body {
+		match transcriber {
+			Transcriber::Token(token) => output.extend([token.to_token_tree()]),
+			Transcriber::Group(delimiter, inner) => {
+				let mut stream = TokenStream::new();
+				transcribe(inner, bindings, indexes, krate, &mut stream)?;
+				output.extend([TokenTree::Group(Group::new(*delimiter, stream))]);
+			},
+			Transcriber::Crate => output.extend([TokenTree::Ident(proc_macro2::Ident::new(krate, proc_macro2::Span::call_site()))]),
+			Transcriber::Variable(name) => match lookup(bindings, name, indexes) {
+				Some(Binding::Single(tokens)) => output.extend(tokens.clone()),
+				Some(Binding::Repeated(_)) => return Err(format!("metavariable `{}` is still repeating at this depth", name)),
+				// Dollar signs that aren't metavariables are kept
+				None => output.extend(quote::quote! { $ }.into_iter().chain([TokenTree::Ident(proc_macro2::Ident::new(name, proc_macro2::Span::call_site()))])),
+			},
+			Transcriber::Repeat(inner, separator) => {
+				for index in 0..repetitions(inner, bindings, indexes)? {
+					if let (true, Some(separator)) = (index > 0, separator) {
+						output.extend([separator.to_token_tree()]);
+					}
+					let indexes = [indexes, &[index]].concat();
+					transcribe(inner, bindings, &indexes, krate, output)?;
+				}
+			},
+		}
+	}
+	Ok(())
+}
+
+impl State {
+	/// Expand an invocation of a `macro_rules!` macro of the workspace, None is returned when the macro isn't one
+	/// Macros that are found but can't be expanded are added to the diagnostics and expand to nothing, macros whose rules
+	/// can't be parsed were already reported at their definition.
+	pub fn expand_macro(&mut self, mac: &syn::Macro) -> Option<TokenStream> {
+		let segments: Vec<String> = mac.path.segments.iter().map(|segment| segment.ident.to_string()).collect();
+		self.record(Lookup::Macro(segments.clone(), self.current_crate.clone(), self.current_file.clone()));
+		let index = self.index.clone();
+		let (definition, rules) = index.find_macro(&segments, &self.current_crate, &self.current_file)?;
+		let rules = match rules {
+			Some(rules) => rules,
+			None => return Some(TokenStream::new()),
+		};
+
+		if self.macro_depth >= MAX_DEPTH {
+			self.macro_error(mac, format!("expansions are nested more than {} levels deep", MAX_DEPTH));
+			return Some(TokenStream::new());
+		}
+
+		let krate = if definition.krate == self.current_crate { "crate" } else { &definition.krate };
+		match rules.expand(mac.tokens.clone(), krate) {
+			Ok(tokens) => Some(tokens),
+			Err(message) => {
+				self.macro_error(mac, message);
+				Some(TokenStream::new())
+			},
+		}
+	}
+
+	/// Add a macro that couldn't be expanded to the diagnostics
+	pub fn macro_error(&mut self, mac: &syn::Macro, message: String) {
+		let ident = match mac.path.segments.last() {
+			Some(segment) => &segment.ident,
+			None => return,
+		};
+		let start = ident.span().start();
+		self.diagnostics.push(Error::MacroExpansion {path: self.current_file.clone(), name: ident.to_string(), message, span: Span::new(start.line, start.column + 1)});
+	}
+
+	/// Visit the expansion of a macro that is used as an expression
+	pub fn visit_expanded_expr(&mut self, mac: &syn::Macro, tokens: TokenStream) {
+		if tokens.is_empty() {
+			return;
+		}
+		self.enter_expansion(mac);
+		match syn::parse2::<syn::Expr>(tokens.clone()) {
+			Ok(expr) => self.visit_expr(&expr),
+			// Expansions with several statements are visited one after the other
+			Err(_) => match syn::Block::parse_within.parse2(tokens) {
+				Ok(stmts) => stmts.iter().for_each(|stmt| self.visit_stmt(stmt)),
+				Err(err) => self.macro_error(mac, format!("the expansion isn't an expression: {}", err)),
+			},
+		}
+		self.exit_expansion();
+	}
+
+	/// Visit the expansion of a macro that is used as a statement
+	pub fn visit_expanded_stmts(&mut self, mac: &syn::Macro, tokens: TokenStream) {
+		self.enter_expansion(mac);
+		match syn::Block::parse_within.parse2(tokens) {
+			Ok(stmts) => for stmt in &stmts {
+				self.visit_stmt(stmt);
+				self.graph.clear_calls_layer();
+			},
+			Err(err) => self.macro_error(mac, format!("the expansion isn't a list of statements: {}", err)),
+		}
+		self.exit_expansion();
+	}
+
+	/// Visit the expansion of a macro that is used as an item
+	pub fn visit_expanded_items(&mut self, mac: &syn::Macro, tokens: TokenStream) {
+		self.enter_expansion(mac);
+		match syn::parse2::<syn::File>(tokens) {
+			Ok(file) => file.items.iter().for_each(|item| self.visit_item(item)),
+			Err(err) => self.macro_error(mac, format!("the expansion isn't a list of items: {}", err)),
+		}
+		self.exit_expansion();
+	}
+
+	/// Start visiting an expansion, everything in it is placed on the line of the outermost invocation
+	fn enter_expansion(&mut self, mac: &syn::Macro) {
+		if self.macro_depth == 0 {
+			self.expansion_line = Some(mac.bang_token.span.start().line);
+		}
+		self.macro_depth += 1;
+	}
+
+	fn exit_expansion(&mut self) {
+		self.macro_depth -= 1;
+		if self.macro_depth == 0 {
+			self.expansion_line = None;
+		}
+	}
+}
@@ -0,0 +1,143 @@
+/*-------------
+/parse/macros.rs
+
+Built-in macros whose arguments are ordinary Rust expressions are parsed here so the calls inside of them end up in the
+graph. Formatting macros keep their format string as a template, `vec!`, `assert!`, `matches!` and `json!` have a syntax
+of their own that is parsed into the expressions they contain. Every other macro stays opaque.
+-------------*/
+use syn::parse::{ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::{token, Expr, Token};
+
+/// Macros that take a format string followed by the arguments
+const FORMAT_MACROS: [&str; 17] = [
+	"format", "format_args", "print", "println", "eprint", "eprintln", "panic", "todo", "unimplemented", "unreachable",
+	"trace", "debug", "info", "warn", "error", "bail", "anyhow",
+];
+
+/// Macros that take conditions or values before the optional format string
+const ASSERT_MACROS: [(&str, usize); 6] = [
+	("assert", 1), ("debug_assert", 1), ("assert_eq", 2), ("assert_ne", 2), ("debug_assert_eq", 2), ("debug_assert_ne", 2),
+];
+
+/// The arguments of a built-in macro
+#[derive(Clone, Default)]
+pub struct MacroArgs {
+	/// The format string of a formatting macro Eg. "Hello {}!"
+	pub template: Option<String>,
+	/// The arguments that are ordinary expressions
+	pub exprs: Vec<Expr>,
+}
+
+/// Parse the arguments of a built-in macro, None is returned for other macros or arguments that don't parse
+pub fn parse_args(mac: &syn::Macro) -> Option<MacroArgs> {
+	let name = mac.path.segments.last()?.ident.to_string();
+
+	if FORMAT_MACROS.contains(&name.as_str()) {
+		return split_template(parse_exprs(mac)?, 0);
+	}
+	if name == "write" || name == "writeln" {
+		return split_template(parse_exprs(mac)?, 1);
+	}
+	if let Some((_, conditions)) = ASSERT_MACROS.iter().find(|(assert, _)| *assert == name) {
+		return split_template(parse_exprs(mac)?, *conditions);
+	}
+
+	match name.as_str() {
+		"vec" => parse_vec(mac),
+		"dbg" => Some(MacroArgs {template: None, exprs: parse_exprs(mac)?}),
+		"matches" => mac.parse_body_with(parse_matches).ok(),
+		"json" => mac.parse_body_with(|input: ParseStream| {
+			let mut exprs = Vec::new();
+			parse_json(input, &mut exprs)?;
+			Ok(MacroArgs {template: None, exprs})
+		}).ok(),
+		_ => None,
+	}
+}
+
+/// Parse arguments separated by commas
+fn parse_exprs(mac: &syn::Macro) -> Option<Vec<Expr>> {
+	let exprs = mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated).ok()?;
+	Some(exprs.into_iter().collect())
+}
+
+/// Split the format string from the arguments, it comes after the leading arguments
+/// Named arguments Eg. `name = value` are visited as their value.
+fn split_template(exprs: Vec<Expr>, leading: usize) -> Option<MacroArgs> {
+	let mut args = MacroArgs::default();
+	for (i, expr) in exprs.into_iter().enumerate() {
+		match expr {
+			Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(template), .. }) if i == leading => args.template = Some(template.value()),
+			Expr::Assign(assign) if i > leading => args.exprs.push(*assign.right),
+			expr => args.exprs.push(expr),
+		}
+	}
+	Some(args)
+}
+
+/// Parse `vec![a, b]` and `vec![value; length]`
+fn parse_vec(mac: &syn::Macro) -> Option<MacroArgs> {
+	if let Some(exprs) = parse_exprs(mac) {
+		return Some(MacroArgs {template: None, exprs});
+	}
+
+	let repeat = |input: ParseStream| {
+		let value: Expr = input.parse()?;
+		input.parse::<Token![;]>()?;
+		let length: Expr = input.parse()?;
+		Ok(vec![value, length])
+	};
+	Some(MacroArgs {template: None, exprs: repeat.parse2(mac.tokens.clone()).ok()?})
+}
+
+/// Parse `matches!(expression, pattern if guard)`
+fn parse_matches(input: ParseStream) -> syn::Result<MacroArgs> {
+	let mut exprs = vec![input.parse::<Expr>()?];
+	input.parse::<Token![,]>()?;
+	syn::Pat::parse_multi_with_leading_vert(input)?;
+	if input.parse::<Option<Token![if]>>()?.is_some() {
+		exprs.push(input.parse()?);
+	}
+	input.parse::<Option<Token![,]>>()?;
+	Ok(MacroArgs {template: None, exprs})
+}
+
+/// Parse a json value, the expressions that are interpolated into it are collected
+fn parse_json(input: ParseStream, exprs: &mut Vec<Expr>) -> syn::Result<()> {
+	if input.peek(token::Brace) {
+		let content;
+		syn::braced!(content in input);
+		while !content.is_empty() {
+			// Keys are string literals, identifiers or expressions in brackets
+			if content.peek(token::Bracket) {
+				let key;
+				syn::bracketed!(key in content);
+				exprs.push(key.parse()?);
+			} else {
+				content.parse::<proc_macro2::TokenTree>()?;
+			}
+			content.parse::<Token![:]>()?;
+			parse_json(&content, exprs)?;
+			if !content.is_empty() {
+				content.parse::<Token![,]>()?;
+			}
+		}
+	} else if input.peek(token::Bracket) {
+		let content;
+		syn::bracketed!(content in input);
+		while !content.is_empty() {
+			parse_json(&content, exprs)?;
+			if !content.is_empty() {
+				content.parse::<Token![,]>()?;
+			}
+		}
+	} else {
+		match input.parse::<Expr>()? {
+			Expr::Path(path) if ["null", "true", "false"].iter().any(|value| path.path.is_ident(value)) => {},
+			Expr::Lit(_) => {},
+			expr => exprs.push(expr),
+		}
+	}
+	Ok(())
+}
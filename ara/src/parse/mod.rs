@@ -5,195 +5,335 @@ Parse is the file that does the bulk of the lifting to make a call stack of the
 be stitched together in the engine. The main logic is in the visit_expr function, which is the main function that traverses the block. The visit_expr_method_call and visit_expr_call are the other 2 points
 of interested followed by visit_expr which contains the routing logic. All other functions are helper functions to make the code more readable and to keep the main logic clean.
 -------------*/
-use syn::{visit::Visit, ItemFn};
+use syn::{spanned::Spanned, visit::{self, Visit}, ItemFn};
+use crate::cfg;
+use crate::graph::Graph;
 use crate::graph::node::{Node, NodeType};
+use crate::index::TypeRef;
+use crate::scope::{BlockScope, Local};
 use crate::state::State;
-use crate::log::{self, info};
-use crate::config::APP;
 
-mod utils;
+pub mod expand;
+mod macros;
+pub(crate) mod utils;
 
 impl<'ast> Visit<'ast> for State {
-	/// Visit functions and add them to the calls
-	fn visit_item_fn(&mut self, func: &'ast ItemFn) {
-		if !APP.function_name.contains(&func.sig.ident.to_string()) && !APP.function_name.is_empty() {
+	/// Visit a file, a file whose inner cfg attributes don't hold is skipped as a whole
+	fn visit_file(&mut self, i: &'ast syn::File) {
+		if !self.cfg.is_enabled(&i.attrs) {
 			return
 		}
 
-		// Create the call stack with the function as the first part
-		//let args = utils::get_function_arguments(func.sig.inputs.clone());
-		let name = func.sig.ident.to_string();
-		let path = self.current_file.clone();
-		let comments = utils::get_doc_comments(func);
-
-		let path_name = format!("{}::{}", path, name);
-		let mut node = Node::new(&path_name, NodeType::Function);
-		let node = node.add_comments(comments);
-		self.graph.add_node(node.clone());
-
-		log::info::print_visit_function(&func.sig.ident.to_string(), &self.current_file);
-		self.visit_block(&func.block);
-
-		self.graph.clear_calls();
-
-		// Debug
-		println!("Graph: {:#?}", self.graph);
-    }
-
-	/// Visits the block of the code and determines for each statement where the block is called to
-	fn visit_block(&mut self, block: &'ast syn::Block) {
-		self.graph.increase_layer();
-		for stmt in &block.stmts {
-			if self.graph.get_depth() == 1 {
-			 	info::print_loc(&quote! { #stmt }.to_string());
-			}
-
-			if let syn::Stmt::Local(local) = stmt {
-				self.visit_local(local);
-			}
-
-			if let syn::Stmt::Expr(expr, _) = stmt {
-				self.visit_expr(expr);
-			}
-			self.graph.clear_calls_layer();
+		let length = self.current_cfg.len();
+		self.current_cfg.extend(cfg::conditions(&i.attrs));
+		for item in &i.items {
+			self.visit_item(item);
 		}
-		self.graph.decrease_layer();
+		self.current_cfg.truncate(length);
 	}
 
-	/// Main Logic for visiting and traversing the syntax tree
-	fn visit_expr(&mut self, i: &'ast syn::Expr) {
-		log::debug::print_expr(&quote! { #i }.to_string());
-		
-		if let syn::Expr::Match(ref call) = &*i {
-			self.visit_expr_match(call);
+	/// Visit an item when its cfg attributes hold, the conditions are kept for the functions inside of it
+	fn visit_item(&mut self, i: &'ast syn::Item) {
+		let attrs = utils::get_item_attrs(i);
+		if !self.cfg.is_enabled(attrs) {
+			return
 		}
 
-		if let syn::Expr::Try(ref call) = &*i {
-			self.visit_expr(&call.expr);
-		}
+		let length = self.current_cfg.len();
+		self.current_cfg.extend(cfg::conditions(attrs));
+		visit::visit_item(self, i);
+		self.current_cfg.truncate(length);
+	}
 
-		if let syn::Expr::ForLoop(call) = &i {
-			self.visit_expr(&call.expr);
-		}
+	/// Visit functions and add them to the calls
+	fn visit_item_fn(&mut self, func: &'ast ItemFn) {
+		let key = format!("{}::{}", self.item_prefix(), func.sig.ident);
+		self.visit_function(key, &func.attrs, &func.sig, &func.block);
+	}
 
-		if let syn::Expr::While(call) = &i {
-			self.visit_expr(&call.cond);
-		}
+	/// Visit the methods of an impl block, they're keyed by the type Eg. `crate::users::UserService::find` or by the type
+	/// and the trait Eg. `<crate::users::PgRepo as UserRepo>::find`
+	fn visit_item_impl(&mut self, i: &'ast syn::ItemImpl) {
+		let (self_type, trait_name) = utils::get_impl_names(&self.item_prefix(), i);
+
+		let previous = self.current_impl.replace((self_type.clone(), trait_name.clone()));
+		let bounds = std::mem::replace(&mut self.current_bounds, utils::get_generic_bounds(&i.generics));
+		for item in &i.items {
+			let method = match item {
+				syn::ImplItem::Fn(method) if self.cfg.is_enabled(&method.attrs) => method,
+				_ => continue,
+			};
+			let key = utils::get_method_key(&self_type, trait_name.as_deref(), &method.sig.ident.to_string());
 
-		if let syn::Expr::Block(ref call) = &*i {
-			self.visit_block(&call.block);
+			let length = self.current_cfg.len();
+			self.current_cfg.extend(cfg::conditions(&method.attrs));
+			self.visit_function(key, &method.attrs, &method.sig, &method.block);
+			self.current_cfg.truncate(length);
 		}
+		self.current_bounds = bounds;
+		self.current_impl = previous;
+	}
 
-		if let syn::Expr::Reference(ref call) = &*i {
-			self.visit_expr(&call.expr);
-		}
+	/// Visit the default methods of a trait, they're keyed by the trait Eg. `crate::repo::UserRepo::find`
+	fn visit_item_trait(&mut self, i: &'ast syn::ItemTrait) {
+		let path = format!("{}::{}", self.item_prefix(), i.ident);
+
+		let previous = (self.current_impl.take(), self.current_trait.replace(i.ident.to_string()));
+		let bounds = std::mem::replace(&mut self.current_bounds, utils::get_generic_bounds(&i.generics));
+		for item in &i.items {
+			let (method, block) = match item {
+				syn::TraitItem::Fn(method) if self.cfg.is_enabled(&method.attrs) => match &method.default {
+					Some(block) => (method, block),
+					None => continue,
+				},
+				_ => continue,
+			};
+			let key = format!("{}::{}", path, method.sig.ident);
 
-		if let syn::Expr::Binary(ref call) = &*i {
-			self.visit_expr(&call.left);
-			self.visit_expr(&call.right);
+			let length = self.current_cfg.len();
+			self.current_cfg.extend(cfg::conditions(&method.attrs));
+			self.visit_function(key, &method.attrs, &method.sig, block);
+			self.current_cfg.truncate(length);
 		}
+		self.current_bounds = bounds;
+		(self.current_impl, self.current_trait) = previous;
+	}
 
-		if let syn::Expr::Return(ref call) = &*i {
-			self.visit_expr_return(call);
-		}
+	/// Visits the block of the code and determines for each statement where the block is called to
+	/// The items and imports of the block can only be used inside of it
+	fn visit_block(&mut self, block: &'ast syn::Block) {
+		let items = block.stmts.iter().filter_map(|stmt| match stmt {
+			syn::Stmt::Item(item) if self.cfg.is_enabled(utils::get_item_attrs(item)) => Some(item),
+			_ => None,
+		});
+		self.block_scopes.push(BlockScope::new(items, &self.item_prefix()));
 
-		if let syn::Expr::Await(ref call) = &*i {
-			self.visit_expr(&call.base);
+		self.graph.increase_layer();
+		for stmt in &block.stmts {
+			self.visit_stmt(stmt);
+			self.graph.clear_calls_layer();
 		}
+		self.graph.decrease_layer();
+		self.block_scopes.pop();
+	}
 
-		if let syn::Expr::If(ref call) = &*i {
-			self.visit_expr_if(call);
+	/// Visit a statement of a block when its cfg attributes hold
+	fn visit_stmt(&mut self, stmt: &'ast syn::Stmt) {
+		if !self.cfg.is_enabled(utils::get_stmt_attrs(stmt)) {
+			return
 		}
 
-		if let syn::Expr::Tuple(ref _call) = &*i {
-			let node = Node::new("(,)", NodeType::Tuple);
-			let node_id = self.graph.add_node(node).id;
-			self.graph.add_edge(node_id);
-
-			self.graph.increase_layer_args();
-			for el in _call.elems.iter() {
-				self.visit_expr(el);
-			}
-			self.graph.decrease_layer_args();
+		if self.graph.get_depth() == 1 {
+			self.log.print_loc(&quote! { #stmt }.to_string());
 		}
 
-		if let syn::Expr::Call(ref call) = &*i {
-			self.visit_expr_call(call);
+		match stmt {
+			syn::Stmt::Local(local) => self.visit_local(local),
+			syn::Stmt::Expr(expr, _) => self.visit_expr(expr),
+			syn::Stmt::Macro(stmt) => match self.expand_macro(&stmt.mac) {
+				Some(tokens) => self.visit_expanded_stmts(&stmt.mac, tokens),
+				None => self.visit_macro(&stmt.mac),
+			},
+			syn::Stmt::Item(item) => self.visit_block_item(item),
 		}
+	}
 
-		if let syn::Expr::Paren(ref call) = &*i {
-			let node = Node::new("()", NodeType::Paren);
-			let node_id = self.graph.add_node(node).id;
-			self.graph.add_edge(node_id);
-
-			self.graph.increase_layer_args();
-			self.visit_expr(&call.expr);
-			self.graph.decrease_layer_args();
-		}
- 
-		if let syn::Expr::MethodCall(ref call) = &*i {
-			self.visit_expr_method_call(call);
+	/// Main Logic for visiting and traversing the syntax tree
+	/// Every kind of expression is visited for the calls inside of it, expressions that aren't understood are added to
+	/// the unsupported constructs.
+	fn visit_expr(&mut self, i: &'ast syn::Expr) {
+		self.log.print_expr(&quote! { #i }.to_string());
+
+		match i {
+			syn::Expr::Match(call) => self.visit_expr_match(call),
+			syn::Expr::Try(call) => self.visit_expr(&call.expr),
+			syn::Expr::ForLoop(call) => {
+				self.visit_expr(&call.expr);
+				self.block_scopes.push(BlockScope::default());
+				self.bind_pattern(&call.pat);
+				self.visit_block(&call.body);
+				self.block_scopes.pop();
+			},
+			// The bindings of `while let` are in the scope of the body
+			syn::Expr::While(call) => {
+				self.block_scopes.push(BlockScope::default());
+				self.visit_expr(&call.cond);
+				self.visit_block(&call.body);
+				self.block_scopes.pop();
+			},
+			syn::Expr::Loop(call) => self.visit_block(&call.body),
+			syn::Expr::Block(call) => self.visit_block(&call.block),
+			syn::Expr::Async(call) => self.visit_block(&call.block),
+			syn::Expr::Unsafe(call) => self.visit_block(&call.block),
+			syn::Expr::TryBlock(call) => self.visit_block(&call.block),
+			syn::Expr::Const(call) => self.visit_block(&call.block),
+			syn::Expr::Closure(call) => self.visit_expr_closure(call),
+			// Invisible groups come from the fragments of expanded macros
+			syn::Expr::Group(call) => self.visit_expr(&call.expr),
+			syn::Expr::Reference(call) => self.visit_expr(&call.expr),
+			syn::Expr::RawAddr(call) => self.visit_expr(&call.expr),
+			syn::Expr::Unary(call) => self.visit_expr(&call.expr),
+			syn::Expr::Cast(call) => self.visit_expr(&call.expr),
+			syn::Expr::Field(call) => self.visit_expr(&call.base),
+			syn::Expr::Let(call) => {
+				self.visit_expr(&call.expr);
+				self.bind_pattern(&call.pat);
+			},
+			syn::Expr::Binary(call) => {
+				self.visit_expr(&call.left);
+				self.visit_expr(&call.right);
+			},
+			syn::Expr::Assign(call) => {
+				self.visit_expr(&call.left);
+				self.visit_expr(&call.right);
+			},
+			syn::Expr::Index(call) => {
+				self.visit_expr(&call.expr);
+				self.visit_expr(&call.index);
+			},
+			syn::Expr::Range(call) => {
+				for expr in call.start.iter().chain(call.end.iter()) {
+					self.visit_expr(expr);
+				}
+			},
+			syn::Expr::Array(call) => {
+				for el in call.elems.iter() {
+					self.visit_expr(el);
+				}
+			},
+			syn::Expr::Repeat(call) => {
+				self.visit_expr(&call.expr);
+				self.visit_expr(&call.len);
+			},
+			syn::Expr::Return(call) => self.visit_expr_return(call),
+			syn::Expr::Break(call) => {
+				if let Some(expr) = &call.expr {
+					self.visit_expr(expr);
+				}
+			},
+			syn::Expr::Yield(call) => {
+				if let Some(expr) = &call.expr {
+					self.visit_expr(expr);
+				}
+			},
+			syn::Expr::Await(call) => self.visit_expr(&call.base),
+			syn::Expr::If(call) => self.visit_expr_if(call),
+			syn::Expr::Tuple(call) => {
+				let node = Node::new("(,)", NodeType::Tuple);
+				let node_id = self.graph.add_node(node);
+				self.graph.add_edge(node_id);
+
+				self.graph.increase_layer_args();
+				for el in call.elems.iter() {
+					self.visit_expr(el);
+				}
+				self.graph.decrease_layer_args();
+			},
+			syn::Expr::Call(call) => self.visit_expr_call(call),
+			syn::Expr::Paren(call) => {
+				let node = Node::new("()", NodeType::Paren);
+				let node_id = self.graph.add_node(node);
+				self.graph.add_edge(node_id);
+
+				self.graph.increase_layer_args();
+				self.visit_expr(&call.expr);
+				self.graph.decrease_layer_args();
+			},
+			syn::Expr::MethodCall(call) => self.visit_expr_method_call(call),
+			syn::Expr::Struct(call) => {
+				let path = call.path.segments.iter().map(|f| f.ident.to_string()).collect::<Vec<_>>().join("");
+				let node = Node::new(&path, NodeType::Struct);
+				self.graph.add_node(node);
+
+				for field in &call.fields {
+					self.visit_expr(&field.expr);
+				}
+				if let Some(rest) = &call.rest {
+					self.visit_expr(rest);
+				}
+			},
+			syn::Expr::Macro(call) => self.visit_macro(&call.mac),
+			syn::Expr::Path(path) => {
+				// Check if the path is a local variable, calls get the canonical path of the item
+				let node = if path.path.get_ident().is_some_and(|ident| self.get_local(&ident.to_string()).is_some()) {
+					Node::new(&utils::create_path(path), NodeType::Local)
+				} else {
+					let mut node = Node::new(&utils::create_path(path), NodeType::Call);
+					let segments: Vec<String> = path.path.segments.iter().map(|segment| segment.ident.to_string()).collect();
+					node.add_path(Some(self.item_path(&segments)));
+					node
+				};
+				let node_id = self.graph.add_node(node);
+				self.graph.add_edge(node_id);
+			},
+			syn::Expr::Lit(call) => self.graph.add_literal(&quote!{#call}.to_string()),
+			// There are no calls in these
+			syn::Expr::Continue(_) | syn::Expr::Infer(_) => {},
+			i => self.add_unsupported("expression", i.span()),
 		}
+	}
 
+	/// Visit a method call and add it to the nodes
+	/// Calls on a receiver whose type is known are resolved to the methods of the workspace they can end up in
+	fn visit_expr_method_call(&mut self, i: &'ast syn::ExprMethodCall) {
+		self.visit_expr(&i.receiver);
 
-		// if self.calls.get_biggest_depth() == 0 {
-		// 	if let syn::Expr::Field(ref call) = &*i {
-		// 		if let syn::Expr::Path(ref path) = &*call.base {
-		// 			self.calls.add_call(&utils::create_path(path), CallType::Field);
-		// 		}
-		// 		self.visit_expr(&call.base);
-		// 		if let syn::Member::Named(ident) = &call.member {
-		// 			println!("Field: {}", ident.to_string());
-		// 			self.calls.add_field(ident.to_string());
-		// 		}
-		// 	}
-		// }
-
-		if let syn::Expr::Struct(ref call) = &*i {
-			let path = call.path.segments.iter().map(|f| f.ident.to_string()).collect::<Vec<_>>().join("");
-			let node = Node::new(&path, NodeType::Struct);
-			self.graph.add_node(node);
-
-			for field in &call.fields {
-				self.visit_expr(&field.expr);
-			}
+		let mut node = Node::new(&i.method.to_string(), NodeType::Method);
+		let resolved = self.infer_type(&i.receiver).and_then(|receiver| self.resolve_method(&receiver, &i.method.to_string()));
+		if let Some((dispatch, candidates)) = resolved {
+			node.add_path(candidates.first().cloned());
+			node.add_dispatch(dispatch, candidates);
 		}
+		let node_id = self.graph.add_node(node);
+		self.graph.add_edge(node_id);
 
-		if let syn::Expr::Macro(ref call) = &*i {
-			let path = &call.mac.path;
-			let expr_path = syn::ExprPath { attrs: Vec::new(), qself: None, path: path.clone() };
-			let node = Node::new(&utils::create_path(&expr_path), NodeType::Macro);
-			let node_id = self.graph.add_node(node).id;
-			self.graph.add_edge(node_id);
+		self.graph.increase_layer_args();
+		for arg in &i.args {
+			self.visit_expr(arg);
 		}
+		self.graph.decrease_layer_args();
+	}
 
-		if let syn::Expr::Path(ref path) = &*i {
-			// Check if the path is a local variable
-			let node = if self.graph.local_exists(&utils::create_path(path)) {
-				Node::new(&utils::create_path(path), NodeType::Local)
-			} else {
-				Node::new(&utils::create_path(path), NodeType::Call)
-			};
-			let node_id = self.graph.add_node(node).id;
-			self.graph.add_edge(node_id);
-		}
+	/// Visit a closure, it gets a node of its own so the calls in its body are connected to the closure
+	fn visit_expr_closure(&mut self, i: &'ast syn::ExprClosure) {
+		let node = Node::new(&utils::get_closure_key(i), NodeType::Closure);
+		let node_id = self.graph.add_node(node);
+		self.graph.add_edge(node_id);
 
-		if let syn::Expr::Lit(ref call) = &*i {
-			self.graph.add_literal(&quote!{#call}.to_string())
+		self.block_scopes.push(BlockScope::default());
+		for input in &i.inputs {
+			self.bind_pattern(input);
 		}
+		self.graph.increase_layer_args();
+		self.visit_expr(&i.body);
+		self.graph.decrease_layer_args();
+		self.block_scopes.pop();
 	}
 
-	/// Visit a method call and add it to the nodes
-	fn visit_expr_method_call(&mut self, i: &'ast syn::ExprMethodCall) {
-		self.visit_expr(&i.receiver);
+	/// Visit a macro and add it to the nodes, the arguments of built-in macros are visited like the arguments of a call
+	/// Macros of the workspace are expanded and the expansion is visited instead
+	fn visit_macro(&mut self, i: &'ast syn::Macro) {
+		if let Some(tokens) = self.expand_macro(i) {
+			return self.visit_expanded_expr(i, tokens);
+		}
 
-		let node = Node::new(&i.method.to_string(), NodeType::Method);
-		let node_id = self.graph.add_node(node).id;
+		let path = syn::ExprPath { attrs: Vec::new(), qself: None, path: i.path.clone() };
+		let mut node = Node::new(&utils::create_path(&path), NodeType::Macro);
+		let args = macros::parse_args(i);
+		if let Some(template) = args.as_ref().and_then(|args| args.template.as_ref()) {
+			node.add_literal(template);
+		}
+		let node_id = self.graph.add_node(node);
 		self.graph.add_edge(node_id);
 
+		let exprs = match args {
+			Some(args) => args.exprs,
+			None if i.tokens.is_empty() => return,
+			None => return self.add_unsupported(&format!("macro `{}!`", utils::create_path(&path)), i.span()),
+		};
 		self.graph.increase_layer_args();
-		for arg in &i.args {
-			self.visit_expr(arg);
+		for expr in &exprs {
+			self.visit_expr(expr);
 		}
 		self.graph.decrease_layer_args();
 	}
@@ -208,127 +348,317 @@ impl<'ast> Visit<'ast> for State {
 		self.graph.decrease_layer_args();
 	}
 
-	///
+	/// Visit the guard and the body of a match arm
 	fn visit_arm(&mut self, arm: &'ast syn::Arm) {
+		if !self.cfg.is_enabled(&arm.attrs) {
+			return
+		}
+		self.block_scopes.push(BlockScope::default());
+		self.bind_pattern(&arm.pat);
+		if let Some((_, guard)) = &arm.guard {
+			self.visit_expr(guard);
+		}
 		self.visit_expr(&arm.body);
+		self.block_scopes.pop();
 	}
 
-	/// 
+	/// Visit the returned expression
 	fn visit_expr_return(&mut self, i: &'ast syn::ExprReturn) {
 		if let Some(expr) = &i.expr {
 			self.visit_expr(expr);
 		}
 	}
 
-	///
+	/// Visit the matched expression and every arm of a match
 	fn visit_expr_match(&mut self, call: &'ast syn::ExprMatch) {
-		if let syn::Expr::Call(call) = &*call.expr {
-			self.visit_expr_call(call);
-		}
-
-		if let syn::Expr::MethodCall(call) = &*call.expr {
-			self.visit_expr_method_call(call);
+		self.visit_expr(&call.expr);
+		for arm in &call.arms {
+			self.visit_arm(arm);
 		}
+	}
 
-		if let syn::Expr::Await(call) = &*call.expr {
-			self.visit_expr(&call.base);
+	/// Visit the condition and branches of an if expression, the bindings of `if let` are in the scope of the then branch
+	fn visit_expr_if(&mut self, call: &'ast syn::ExprIf) {
+		self.block_scopes.push(BlockScope::default());
+		self.visit_expr(&call.cond);
+		self.visit_block(&call.then_branch);
+		self.block_scopes.pop();
+		if let Some((_, else_expr)) = &call.else_branch {
+			self.visit_expr(else_expr);
 		}
+	}
 
-		if let syn::Expr::Block(call) = &*call.expr {
-			self.visit_block(&call.block);
+	/// Visit a local variable and get the expression, the locals are bound afterwards so the expression can use the
+	/// locals they shadow Eg. `let id = id.parse()?`
+	fn visit_local(&mut self, i: &'ast syn::Local) {
+		let bindings = utils::get_bindings(&i.pat);
+		if let Some(init) = &i.init {
+			// The node of the expression is marked with the names it is bound to
+			let since = self.graph.get_node_len();
+			self.visit_expr(&init.expr);
+			if !bindings.is_empty() {
+				self.graph.add_local(since, &bindings.join(", "));
+			}
+			// The else branch of `let ... else`
+			if let Some((_, diverge)) = &init.diverge {
+				self.visit_expr(diverge);
+			}
 		}
 
-		for arm in &call.arms {
-			self.visit_arm(arm);
+		// A local of a single name gets the type it is annotated with, or the type of its expression
+		let ty = match (&i.pat, &i.init) {
+			(syn::Pat::Type(pat), _) if bindings.len() == 1 => self.type_ref(&pat.ty),
+			(syn::Pat::Ident(_), Some(init)) if init.diverge.is_none() => self.infer_type(&init.expr),
+			_ => None,
+		};
+		let mut locals: Vec<Local> = bindings.into_iter().map(|name| Local {name, ty: None}).collect();
+		if let [local] = locals.as_mut_slice() {
+			local.ty = ty;
 		}
+		self.bind(locals);
 	}
 
-	///
-	fn visit_expr_if(&mut self, call: &'ast syn::ExprIf) {
-		self.visit_expr(&call.cond);
+	/// Structs are in the type registry of the index, there are no calls in them to visit
+	fn visit_item_struct(&mut self, _i: &'ast syn::ItemStruct) {}
 
-		if let syn::Expr::Unary(unary) = &*call.cond {
-			self.visit_expr(&unary.expr);
-		}
+	/// Enums are in the type registry of the index, there are no calls in them to visit
+	fn visit_item_enum(&mut self, _i: &'ast syn::ItemEnum) {}
+
+	/// Unions are in the type registry of the index, there are no calls in them to visit
+	fn visit_item_union(&mut self, _i: &'ast syn::ItemUnion) {}
+
+	/// Type aliases are in the type registry of the index, the lengths of the arrays in them aren't visited
+	fn visit_item_type(&mut self, _i: &'ast syn::ItemType) {}
+
+	/// The functions of extern blocks don't have bodies to visit
+	fn visit_item_foreign_mod(&mut self, _i: &'ast syn::ItemForeignMod) {}
+
+	/// Visit the initializer of a constant with a graph of its own
+	fn visit_item_const(&mut self, i: &'ast syn::ItemConst) {
+		self.visit_initializer(&i.ident, &i.attrs, &i.expr);
+	}
+
+	/// Visit the initializer of a static with a graph of its own
+	fn visit_item_static(&mut self, i: &'ast syn::ItemStatic) {
+		self.visit_initializer(&i.ident, &i.attrs, &i.expr);
+	}
 
-		if let syn::Expr::Let(let_expr) = &*call.cond {
-			self.visit_expr(&let_expr.expr);
+	/// Visit a macro in the place of an item, macros of the workspace are expanded into items
+	/// The expanded items aren't in the index, so paths to them aren't resolved.
+	fn visit_item_macro(&mut self, i: &'ast syn::ItemMacro) {
+		// `macro_rules!` definitions are in the index
+		if i.ident.is_some() {
+			return
 		}
-		
-		if let syn::Expr::Call(call) = &*call.cond {
-			self.visit_expr_call(call);
+		if let Some(tokens) = self.expand_macro(&i.mac) {
+			self.visit_expanded_items(&i.mac, tokens);
 		}
+	}
 
-		if let syn::Expr::MethodCall(call) = &*call.cond {
-			self.visit_expr_method_call(call);
+	/// Visit a module, inline modules are visited with their own module path and declared modules are files of their own
+	fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+		if let Some((_, items)) = &i.content {
+			self.current_module.push(i.ident.to_string());
+			for item in items {
+				self.visit_item(item);
+			}
+			self.current_module.pop();
 		}
+	}
 
-		if let Some((_, else_expr)) = &call.else_branch {
-			self.visit_expr(else_expr);
+	/// Imports are in the scopes of the index, there are no calls in them to visit
+	fn visit_item_use(&mut self, _i: &'ast syn::ItemUse) {}
+}
+
+impl State {
+	/// Visit the body of a function or a method with a graph of its own, functions that aren't in the function filter
+	/// are skipped
+	fn visit_function(&mut self, key: String, attrs: &[syn::Attribute], sig: &syn::Signature, block: &syn::Block) {
+		if !self.options.includes_function(&sig.ident.to_string()) {
+			return
 		}
 
-		self.visit_block(&call.then_branch);
+		// Items in the body are under the function, methods use the type instead of the trait Eg. "crate::repo::PgRepo::find"
+		self.current_function = Some(match &self.current_impl {
+			Some((self_type, _)) => format!("{}::{}", self_type, sig.ident),
+			None => key.clone(),
+		});
+
+		// Create the call stack with the function as the first part
+		let mut node = Node::new(&key, NodeType::Function);
+		let node = node.add_comments(utils::get_doc_comments(attrs));
+		self.start_function();
+		self.graph.add_node(node.clone());
+
+		self.log.print_visit_function(&sig.ident.to_string(), &self.current_file);
+		// The generics of the function bound the types in its body
+		let bounds = self.current_bounds.len();
+		self.current_bounds.extend(utils::get_generic_bounds(&sig.generics));
+		let parameters = BlockScope {locals: self.bindings(sig), ..BlockScope::default()};
+		self.block_scopes.push(parameters);
+		self.visit_block(block);
+		self.block_scopes.pop();
+		self.current_bounds.truncate(bounds);
+
+		self.graph.clear_calls();
+		self.finish_function(key, utils::get_receiver(sig));
+		self.current_function = None;
 	}
 
-	/// Visit a local variable and get the expression
-	fn visit_local(&mut self, i: &'ast syn::Local) {
-		if let Some(init) = &i.init {
-			self.visit_expr(&*init.expr);
+	/// Visit the initializer of a constant or a static with a graph of its own that is keyed by the path of the item
+	/// Eg. `crate::limits::MAX`, the calls in it are linked like the calls of a function
+	fn visit_initializer(&mut self, ident: &syn::Ident, attrs: &[syn::Attribute], expr: &syn::Expr) {
+		if !self.options.includes_function(&ident.to_string()) {
+			return
 		}
 
-		if let Some(local) = utils::get_local(i) {
-			self.graph.add_local(&local);
-		}
-	}
+		let key = format!("{}::{}", self.item_prefix(), ident);
+		let previous = self.current_function.replace(key.clone());
+		let mut node = Node::new(&key, NodeType::Function);
+		let node = node.add_comments(utils::get_doc_comments(attrs));
+		self.start_function();
+		self.graph.add_node(node.clone());
 
-	/// Visit a struct and add it to the calls
-	fn visit_item_struct(&mut self, _i: &'ast syn::ItemStruct) {
-		//let route = format!("{}::{}", self.current_file, &i.ident.to_string());
-		//let node = Node::new(self.graph.get_node_len(), route, NodeType::Struct);
-		//self.graph.add_node(node);
+		// The initializer is visited like the only statement of a block
+		self.block_scopes.push(BlockScope::default());
+		self.graph.increase_layer();
+		self.visit_expr(expr);
+		self.graph.decrease_layer();
+		self.block_scopes.pop();
+
+		self.graph.clear_calls();
+		self.finish_function(key, None);
+		self.current_function = previous;
 	}
 
-	/// Visit an enum and add it to the calls
-	fn visit_item_enum(&mut self, _i: &'ast syn::ItemEnum) {
-		//let route = format!("{}::{}", self.current_file, &i.ident.to_string());
-		//let node = Node::new(self.graph.get_node_len(), route, NodeType::Enum);
-		//self.graph.add_node(node);
+	/// Visit an item in a block, functions, impl blocks, constants and statics are visited with a graph of their own and
+	/// the function that is being visited is continued afterwards
+	fn visit_block_item(&mut self, item: &syn::Item) {
+		match item {
+			syn::Item::Fn(_) | syn::Item::Impl(_) | syn::Item::Trait(_) | syn::Item::Const(_) | syn::Item::Static(_) => {
+				// Items in a function can't use its locals, and items in a method can't use `Self` or the generics of the impl block
+				let graph = std::mem::replace(&mut self.graph, Graph::new(self.log));
+				let scopes = self.block_scopes.clone();
+				self.block_scopes.iter_mut().for_each(|scope| scope.locals.clear());
+				let bounds = std::mem::take(&mut self.current_bounds);
+				let current_impl = self.current_impl.take();
+				let current_trait = self.current_trait.take();
+				let function = self.current_function.clone();
+
+				self.visit_item(item);
+
+				self.graph = graph;
+				self.block_scopes = scopes;
+				self.current_bounds = bounds;
+				self.current_impl = current_impl;
+				self.current_trait = current_trait;
+				self.current_function = function;
+			},
+			// These are in the scope of the block
+			syn::Item::Use(_) | syn::Item::ExternCrate(_) | syn::Item::Struct(_) | syn::Item::Enum(_) | syn::Item::Union(_) |
+			syn::Item::Type(_) => {},
+			item => self.add_unsupported("item in a block", item.span()),
+		}
 	}
 
-	///
-	fn visit_item_impl(&mut self, _i: &'ast syn::ItemImpl) {
-		//println!("Impl: {}", quote! { #i });
+	/// Bind the names of a pattern in the innermost scope
+	fn bind_pattern(&mut self, pat: &syn::Pat) {
+		self.bind(utils::get_bindings(pat).into_iter().map(|name| Local {name, ty: None}));
 	}
 
-	///
-	fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
-		let module = &i.ident.to_string();
-		self.add_new_lib(&module);
+	/// Get the parameters of a function with their types, `self` is the type of the impl block or the trait
+	fn bindings(&self, sig: &syn::Signature) -> Vec<Local> {
+		let mut bindings = Vec::new();
+		for input in &sig.inputs {
+			match input {
+				syn::FnArg::Receiver(_) => bindings.push(Local {name: "self".to_string(), ty: self.self_type()}),
+				syn::FnArg::Typed(typed) => match &*typed.pat {
+					syn::Pat::Ident(pat) => bindings.push(Local {name: pat.ident.to_string(), ty: self.type_ref(&typed.ty)}),
+					pat => bindings.extend(utils::get_bindings(pat).into_iter().map(|name| Local {name, ty: None})),
+				},
+			}
+		}
+		bindings
 	}
 
-	///
-	fn visit_item_use(&mut self, i: &'ast syn::ItemUse) {
-		self.visit_use_tree(&i.tree);
-		self.use_stack.clear();
+	/// Get what is known about a type that is written in the function that is being visited
+	fn type_ref(&self, ty: &syn::Type) -> Option<TypeRef> {
+		utils::get_type_ref(ty, &self.module_path(), &self.current_bounds, self.self_type().as_ref())
 	}
 
-	///
-	fn visit_use_tree(&mut self, i: &'ast syn::UseTree) {
-		match &i {
-			syn::UseTree::Path(path) => {
-				self.use_stack.insert(path.ident.to_string());
-				self.visit_use_tree(&path.tree);
-				self.use_stack.pop_stack();
+	/// Infer the type of an expression from the types of the locals, the types that the functions of the workspace return
+	/// and the fields of the types in the type registry, None is returned when the type isn't known
+	fn infer_type(&self, expr: &syn::Expr) -> Option<TypeRef> {
+		match expr {
+			syn::Expr::Path(path) => {
+				let name = path.path.get_ident()?.to_string();
+				self.get_local(&name)?.ty.clone()
 			},
-			syn::UseTree::Group(group) => {
-				self.use_stack.add_layer();
-				for tree in &group.items {
-					self.visit_use_tree(tree);
+			syn::Expr::Struct(call) => self.type_ref(&syn::Type::Path(syn::TypePath {qself: None, path: call.path.clone()})),
+			syn::Expr::Call(call) => match &*call.func {
+				syn::Expr::Path(path) if path.qself.is_none() => self.infer_call(&path.path),
+				_ => None,
+			},
+			syn::Expr::MethodCall(call) => {
+				let receiver = self.infer_type(&call.receiver)?;
+				let method = call.method.to_string();
+				match self.resolve_method(&receiver, &method) {
+					Some((_, candidates)) => self.return_type(candidates.first()?),
+					None => match (receiver, method.as_str()) {
+						(TypeRef::Wrapped(inner), "unwrap" | "expect" | "unwrap_or_default") => Some(*inner),
+						(receiver, "clone" | "to_owned") => Some(receiver),
+						_ => None,
+					},
 				}
-				self.use_stack.clear_layer();
-			}
-			syn::UseTree::Name(name) => self.add_new_lib(&quote!{#name}.to_string()),
-			_ => {}
+			},
+			syn::Expr::Field(field) => {
+				let base = match self.infer_type(&field.base)? {
+					TypeRef::Concrete(base) => base,
+					_ => return None,
+				};
+				match &field.member {
+					syn::Member::Named(name) => self.field_type(&base, &name.to_string()),
+					syn::Member::Unnamed(index) => self.field_type(&base, &index.index.to_string()),
+				}
+			},
+			syn::Expr::Try(call) => match self.infer_type(&call.expr)? {
+				TypeRef::Wrapped(inner) => Some(*inner),
+				_ => None,
+			},
+			syn::Expr::Cast(call) => self.type_ref(&call.ty),
+			syn::Expr::Await(call) => self.infer_type(&call.base),
+			syn::Expr::Reference(reference) => self.infer_type(&reference.expr),
+			syn::Expr::Paren(paren) => self.infer_type(&paren.expr),
+			syn::Expr::Group(group) => self.infer_type(&group.expr),
+			_ => None,
+		}
+	}
+
+	/// Infer the type a call of a path returns Eg. `load()`, `db::load()`, `User::new()` or `Self::default()`
+	/// Associated functions of the workspace get the type they return, other constructors of a type get the type itself.
+	fn infer_call(&self, path: &syn::Path) -> Option<TypeRef> {
+		let segments: Vec<String> = path.segments.iter().map(|segment| segment.ident.to_string()).collect();
+		let (name, prefix) = segments.split_last()?;
+
+		// The type of an associated function, types start with an uppercase letter
+		let type_name = prefix.last().filter(|segment| segment.starts_with(|c: char| c.is_uppercase()));
+		if type_name.is_some() {
+			let self_type = match self.item_path(prefix) {
+				path if path.contains("::") => TypeRef::Concrete(path),
+				_ if prefix[0] == "Self" => self.self_type()?,
+				path => TypeRef::Concrete(format!("{}::{}", self.module_path(), path)),
+			};
+			let returns = self.resolve_method(&self_type, name).and_then(|(_, candidates)| self.return_type(candidates.first()?));
+			return match returns {
+				Some(returns) => Some(returns),
+				None if ["new", "default"].contains(&name.as_str()) => Some(self_type),
+				None => None,
+			};
 		}
+
+		// Functions are resolved through the imports of the module Eg. `load` of `use crate::db::load`
+		let segments: Vec<String> = self.item_path(&segments).split("::").map(String::from).collect();
+		let path = self.resolve_path(&segments)?;
+		self.return_type(&path)
 	}
-}
\ No newline at end of file
+}
@@ -3,52 +3,24 @@
 
 Utility functions for helping remove boilerplate code from the main logic of the parser.
 -------------*/
-use syn::punctuated::Punctuated;
-use syn::token::Comma;
-use syn::{ExprPath, FnArg, ItemFn};
-
-/// Get the arguments of a function and return them as a vector of strings
-pub fn get_function_arguments(input: Punctuated<FnArg, Comma>) -> Vec<String> {
-	let mut args = Vec::new();
-
-	for arg in &input {
-		if let syn::FnArg::Typed(arg) = arg {
-			// if let syn::Type::Path(ref path) = *arg.ty {
-			// 	println!("TYPE: {}", quote! { #path }.to_string());
-			// }
-
-			if let syn::Pat::TupleStruct(ref pat) = *arg.pat {
-				pat.elems.iter().for_each(|el| {
-					if let syn::Pat::Ident(ref pat) = el {
-						args.push(pat.ident.to_string());
-					}
-				});
-				println!("{:?}", &pat.path.get_ident());
-			}
-
-			if let syn::Pat::Ident(ref pat) = *arg.pat {
-				args.push(pat.ident.to_string());
-			}
-		}
-	}
-
-	args
-}
+use quote::ToTokens;
+use syn::{Attribute, Expr, ExprPath, FnArg, GenericArgument, Generics, Item, ItemImpl, Pat, PathArguments, ReturnType, Signature, Stmt, Type, TypeParamBound, WherePredicate};
+use crate::index::TypeRef;
 
-/// Get document comments from an attr
-pub fn get_doc_comments(function: &ItemFn) -> Vec<String> {
+/// Get document comments from the attributes of an item
+/// Doc attributes that aren't comments are skipped Eg. `#[doc(hidden)]`.
+pub fn get_doc_comments(attrs: &[Attribute]) -> Vec<String> {
 	let mut comments = Vec::new();
-	for attr in &function.attrs {
-		let comment = if attr.path().is_ident("doc") {
-			Some(attr.meta.require_name_value().unwrap().value.clone())
-		} else {
-			None
+	for attr in attrs {
+		let comment = match attr.path().is_ident("doc") {
+			true => attr.meta.require_name_value().ok().map(|name_value| name_value.value.clone()),
+			false => None,
 		};
 
 		let comment = match comment {
 			Some(comment) => comment,
 			None => continue,
-		}; 
+		};
 
 		if let syn::Expr::Lit(expr_lit) = comment {
 			if let syn::Lit::Str(lit) = &expr_lit.lit {
@@ -59,31 +31,282 @@ pub fn get_doc_comments(function: &ItemFn) -> Vec<String> {
 	comments
 }
 
-/// Get the local variable name
-pub fn get_local(i: &syn::Local) -> Option<String> {
-	match i.pat {
-		syn::Pat::Ident(ref pat) => {
-			return Some(pat.ident.to_string())
-		},
-		syn::Pat::Type(ref pat) => {
-			if let syn::Pat::Ident(ref pat) = *pat.pat {
-				return Some(pat.ident.to_string())
+/// Get the names a pattern binds Eg. `(user, Config { name, .. }, [first, rest @ ..])` binds user, name, first and rest
+/// Identifiers that start with an uppercase letter are constants or unit variants Eg. `None`.
+pub fn get_bindings(pat: &Pat) -> Vec<String> {
+	let mut bindings = Vec::new();
+	add_bindings(pat, &mut bindings);
+	bindings
+}
+
+fn add_bindings(pat: &Pat, bindings: &mut Vec<String>) {
+	match pat {
+		Pat::Ident(pat) => {
+			let name = pat.ident.to_string();
+			let constant = name.starts_with(|c: char| c.is_uppercase()) && pat.subpat.is_none() && pat.by_ref.is_none() && pat.mutability.is_none();
+			if !constant {
+				bindings.push(name);
 			}
-			None
-		},
-		syn::Pat::Tuple(ref pat) => {
-			for el in pat.elems.iter() {
-				if let syn::Pat::Ident(ref pat) = el {
-					return Some(pat.ident.to_string())
-				}
+			if let Some((_, subpat)) = &pat.subpat {
+				add_bindings(subpat, bindings);
 			}
-			None
 		},
-		_ => None
+		Pat::Type(pat) => add_bindings(&pat.pat, bindings),
+		Pat::Reference(pat) => add_bindings(&pat.pat, bindings),
+		Pat::Paren(pat) => add_bindings(&pat.pat, bindings),
+		Pat::Tuple(pat) => pat.elems.iter().for_each(|pat| add_bindings(pat, bindings)),
+		Pat::TupleStruct(pat) => pat.elems.iter().for_each(|pat| add_bindings(pat, bindings)),
+		Pat::Slice(pat) => pat.elems.iter().for_each(|pat| add_bindings(pat, bindings)),
+		Pat::Struct(pat) => pat.fields.iter().for_each(|field| add_bindings(&field.pat, bindings)),
+		// Every alternative binds the same names
+		Pat::Or(pat) => if let Some(pat) = pat.cases.first() {
+			add_bindings(pat, bindings);
+		},
+		_ => {},
 	}
 }
 
 /// Create a path from an expression path
 pub fn create_path(path: &ExprPath) -> String {
 	path.path.segments.iter().map(|p| p.ident.to_string()).collect::<Vec<_>>().join("::")
-}
\ No newline at end of file
+}
+
+/// Get the attributes of an item
+pub fn get_item_attrs(item: &Item) -> &[Attribute] {
+	match item {
+		Item::Const(item) => &item.attrs,
+		Item::Enum(item) => &item.attrs,
+		Item::ExternCrate(item) => &item.attrs,
+		Item::Fn(item) => &item.attrs,
+		Item::ForeignMod(item) => &item.attrs,
+		Item::Impl(item) => &item.attrs,
+		Item::Macro(item) => &item.attrs,
+		Item::Mod(item) => &item.attrs,
+		Item::Static(item) => &item.attrs,
+		Item::Struct(item) => &item.attrs,
+		Item::Trait(item) => &item.attrs,
+		Item::TraitAlias(item) => &item.attrs,
+		Item::Type(item) => &item.attrs,
+		Item::Union(item) => &item.attrs,
+		Item::Use(item) => &item.attrs,
+		_ => &[],
+	}
+}
+
+/// Get the attributes of a statement
+pub fn get_stmt_attrs(stmt: &Stmt) -> &[Attribute] {
+	match stmt {
+		Stmt::Local(local) => &local.attrs,
+		Stmt::Item(item) => get_item_attrs(item),
+		Stmt::Expr(expr, _) => get_expr_attrs(expr),
+		Stmt::Macro(mac) => &mac.attrs,
+	}
+}
+
+/// Get the attributes of an expression
+pub fn get_expr_attrs(expr: &Expr) -> &[Attribute] {
+	match expr {
+		Expr::Array(expr) => &expr.attrs,
+		Expr::Assign(expr) => &expr.attrs,
+		Expr::Async(expr) => &expr.attrs,
+		Expr::Await(expr) => &expr.attrs,
+		Expr::Binary(expr) => &expr.attrs,
+		Expr::Block(expr) => &expr.attrs,
+		Expr::Break(expr) => &expr.attrs,
+		Expr::Call(expr) => &expr.attrs,
+		Expr::Cast(expr) => &expr.attrs,
+		Expr::Closure(expr) => &expr.attrs,
+		Expr::Const(expr) => &expr.attrs,
+		Expr::Continue(expr) => &expr.attrs,
+		Expr::Field(expr) => &expr.attrs,
+		Expr::ForLoop(expr) => &expr.attrs,
+		Expr::Group(expr) => &expr.attrs,
+		Expr::If(expr) => &expr.attrs,
+		Expr::Index(expr) => &expr.attrs,
+		Expr::Infer(expr) => &expr.attrs,
+		Expr::Let(expr) => &expr.attrs,
+		Expr::Lit(expr) => &expr.attrs,
+		Expr::Loop(expr) => &expr.attrs,
+		Expr::Macro(expr) => &expr.attrs,
+		Expr::Match(expr) => &expr.attrs,
+		Expr::MethodCall(expr) => &expr.attrs,
+		Expr::Paren(expr) => &expr.attrs,
+		Expr::Path(expr) => &expr.attrs,
+		Expr::Range(expr) => &expr.attrs,
+		Expr::RawAddr(expr) => &expr.attrs,
+		Expr::Reference(expr) => &expr.attrs,
+		Expr::Repeat(expr) => &expr.attrs,
+		Expr::Return(expr) => &expr.attrs,
+		Expr::Struct(expr) => &expr.attrs,
+		Expr::Try(expr) => &expr.attrs,
+		Expr::TryBlock(expr) => &expr.attrs,
+		Expr::Tuple(expr) => &expr.attrs,
+		Expr::Unary(expr) => &expr.attrs,
+		Expr::Unsafe(expr) => &expr.attrs,
+		Expr::While(expr) => &expr.attrs,
+		Expr::Yield(expr) => &expr.attrs,
+		_ => &[],
+	}
+}
+
+/// Get the path of a type without its generics Eg. "Vec<User>" is "Vec", references are looked through
+pub fn get_type_path(ty: &Type) -> String {
+	match ty {
+		Type::Path(path) => path.path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<_>>().join("::"),
+		Type::Reference(reference) => get_type_path(&reference.elem),
+		Type::Paren(paren) => get_type_path(&paren.elem),
+		Type::Group(group) => get_type_path(&group.elem),
+		ty => get_tokens(ty),
+	}
+}
+
+/// Get the tokens of a syntax node without the spaces quote puts between them Eg. "From<String>"
+/// Spaces between words Eg. "dyn Error", after commas and single colons and inside of string literals are kept.
+pub fn get_tokens(tokens: &impl ToTokens) -> String {
+	let source = tokens.to_token_stream().to_string();
+	let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+
+	let mut result = String::new();
+	let mut in_string = false;
+	let mut chars = source.chars().peekable();
+	while let Some(c) = chars.next() {
+		if c == '"' && !result.ends_with('\\') {
+			in_string = !in_string;
+		}
+		if c == ' ' && !in_string {
+			let separator = result.ends_with(',') || (result.ends_with(':') && !result.ends_with("::") && chars.peek() != Some(&':'));
+			let between_words = is_word(result.chars().last()) && is_word(chars.peek().copied());
+			if !separator && !between_words {
+				continue;
+			}
+		}
+		result.push(c);
+	}
+	result
+}
+
+/// Get the self receiver of a method Eg. "&mut self", None is returned for associated functions
+pub fn get_receiver(sig: &Signature) -> Option<String> {
+	match sig.inputs.first()? {
+		FnArg::Receiver(receiver) if receiver.colon_token.is_some() => Some(format!("self: {}", get_tokens(&receiver.ty))),
+		FnArg::Receiver(receiver) => {
+			let reference = match &receiver.reference {
+				Some((_, Some(lifetime))) => format!("&{} ", lifetime),
+				Some((_, None)) => "&".to_string(),
+				None => String::new(),
+			};
+			let mutability = if receiver.mutability.is_some() { "mut " } else { "" };
+			Some(format!("{}{}self", reference, mutability))
+		},
+		FnArg::Typed(_) => None,
+	}
+}
+
+/// Get the type and the trait of an impl block, types with a single segment are defined in the module of the impl block
+pub fn get_impl_names(module: &str, item: &ItemImpl) -> (String, Option<String>) {
+	let self_type = get_type_path(&item.self_ty);
+	let self_type = if self_type.contains("::") || !matches!(&*item.self_ty, Type::Path(_)) { self_type } else { format!("{}::{}", module, self_type) };
+	let trait_name = item.trait_.as_ref().map(|(_, path, _)| get_tokens(path));
+	(self_type, trait_name)
+}
+
+/// Get the key of a method Eg. "crate::users::UserService::find" or "<crate::users::PgRepo as UserRepo>::find"
+pub fn get_method_key(self_type: &str, trait_name: Option<&str>, method: &str) -> String {
+	match trait_name {
+		Some(trait_name) => format!("<{} as {}>::{}", self_type, trait_name, method),
+		None => format!("{}::{}", self_type, method),
+	}
+}
+
+/// Get the traits that bound the generic parameters Eg. `T: UserRepo + Send` and `where T: UserRepo`
+pub fn get_generic_bounds(generics: &Generics) -> Vec<(String, Vec<String>)> {
+	let mut bounds: Vec<(String, Vec<String>)> = generics.type_params()
+		.map(|param| (param.ident.to_string(), get_trait_bounds(param.bounds.iter())))
+		.collect();
+
+	let predicates = generics.where_clause.iter().flat_map(|clause| clause.predicates.iter());
+	for predicate in predicates {
+		let predicate = match predicate {
+			WherePredicate::Type(predicate) => predicate,
+			_ => continue,
+		};
+		let name = get_tokens(&predicate.bounded_ty);
+		let traits = get_trait_bounds(predicate.bounds.iter());
+		match bounds.iter_mut().find(|(param, _)| *param == name) {
+			Some((_, existing)) => existing.extend(traits),
+			None => bounds.push((name, traits)),
+		}
+	}
+	bounds
+}
+
+/// Get the traits of the bounds without the lifetimes
+fn get_trait_bounds<'a>(bounds: impl Iterator<Item = &'a TypeParamBound>) -> Vec<String> {
+	bounds.filter_map(|bound| match bound {
+		TypeParamBound::Trait(bound) => Some(get_tokens(&bound.path)),
+		_ => None,
+	}).collect()
+}
+
+/// Get what is known about a type for resolving the methods that are called on it
+/// References and smart pointers are looked through, `Self` is the type of the impl block or trait that is visited.
+pub fn get_type_ref(ty: &Type, module: &str, bounds: &[(String, Vec<String>)], self_type: Option<&TypeRef>) -> Option<TypeRef> {
+	match ty {
+		Type::Reference(reference) => get_type_ref(&reference.elem, module, bounds, self_type),
+		Type::Paren(paren) => get_type_ref(&paren.elem, module, bounds, self_type),
+		Type::Group(group) => get_type_ref(&group.elem, module, bounds, self_type),
+		Type::TraitObject(object) => Some(TypeRef::Dyn(get_trait_bounds(object.bounds.iter()))),
+		Type::ImplTrait(bound) => Some(TypeRef::Bounded(get_trait_bounds(bound.bounds.iter()))),
+		Type::Path(path) if path.qself.is_none() => {
+			let last = path.path.segments.last()?;
+			if SMART_POINTERS.contains(&last.ident.to_string().as_str()) {
+				if let PathArguments::AngleBracketed(args) = &last.arguments {
+					if let Some(GenericArgument::Type(inner)) = args.args.first() {
+						return get_type_ref(inner, module, bounds, self_type);
+					}
+				}
+			}
+
+			// `Option<T>` and `Result<T, E>` by their last segment so aliases Eg. `io::Result<T>` are included
+			if ["Option", "Result"].contains(&last.ident.to_string().as_str()) {
+				if let PathArguments::AngleBracketed(args) = &last.arguments {
+					if let Some(GenericArgument::Type(inner)) = args.args.first() {
+						return get_type_ref(inner, module, bounds, self_type).map(|inner| TypeRef::Wrapped(Box::new(inner)));
+					}
+				}
+			}
+
+			let name = get_type_path(ty);
+			if name == "Self" {
+				return self_type.cloned();
+			}
+			if let Some((_, traits)) = bounds.iter().find(|(param, _)| *param == name) {
+				return Some(TypeRef::Bounded(traits.clone()));
+			}
+			match name.contains("::") {
+				true => Some(TypeRef::Concrete(name)),
+				false => Some(TypeRef::Concrete(format!("{}::{}", module, name))),
+			}
+		},
+		_ => None,
+	}
+}
+
+/// Get the type a function returns, None when it returns nothing or the type isn't known
+pub fn get_return_type(sig: &Signature, module: &str, bounds: &[(String, Vec<String>)], self_type: Option<&TypeRef>) -> Option<TypeRef> {
+	match &sig.output {
+		ReturnType::Type(_, ty) => get_type_ref(ty, module, bounds, self_type),
+		ReturnType::Default => None,
+	}
+}
+
+/// Types that methods are called through on the type inside of them
+const SMART_POINTERS: [&str; 4] = ["Box", "Arc", "Rc", "Pin"];
+
+/// Get the key of a closure from its parameters Eg. "|x|" or "move |id, _|"
+pub fn get_closure_key(closure: &syn::ExprClosure) -> String {
+	let inputs = closure.inputs.iter().map(get_tokens).collect::<Vec<_>>().join(", ");
+	let capture = if closure.capture.is_some() { "move " } else { "" };
+	let asyncness = if closure.asyncness.is_some() { "async " } else { "" };
+	format!("{}{}|{}|", asyncness, capture, inputs)
+}
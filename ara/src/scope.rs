@@ -0,0 +1,293 @@
+/*-------------
+/scope.rs
+
+This file is for the import table of every module of the workspace. Each module records the items it defines and the
+names it imports, `use` declarations with their renames and globs, `pub use` re-exports and `extern crate` aliases.
+Paths in the code are resolved through the tables to the canonical path of the item they name Eg. `get_health` in the
+module of `use crate::routes::get_health;` is "app::routes::get_health".
+-------------*/
+use std::collections::BTreeMap;
+use serde_derive::{Deserialize, Serialize};
+use syn::{Item, UseTree, Visibility};
+use crate::index::TypeRef;
+
+/// How many imports are followed to resolve a path, imports that refer to each other in a cycle stop here
+const MAX_IMPORTS: usize = 32;
+
+/// The names that were looked up in a module while resolving a path with what they resolved to, and if the private
+/// imports of the module were followed. Glob imports of modules that import each other reach the same names over and
+/// over again, each is only looked up once and names that are still being looked up resolve to nothing.
+type Lookups = BTreeMap<(String, String, bool), Option<String>>;
+
+/// Names of the prelude that are used without an import
+const PRELUDE: [(&str, &str); 16] = [
+	("Option", "std::option::Option"), ("Some", "std::option::Option::Some"), ("None", "std::option::Option::None"),
+	("Result", "std::result::Result"), ("Ok", "std::result::Result::Ok"), ("Err", "std::result::Result::Err"),
+	("String", "std::string::String"), ("ToString", "std::string::ToString"), ("Vec", "std::vec::Vec"),
+	("Box", "std::boxed::Box"), ("Default", "std::default::Default"), ("From", "std::convert::From"),
+	("Into", "std::convert::Into"), ("Iterator", "std::iter::Iterator"), ("Clone", "std::clone::Clone"),
+	("drop", "std::mem::drop"),
+];
+
+/// A name that is imported into a module
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Import {
+	/// The name the import is bound to Eg. "c" for `use a::b as c`, None for a glob import
+	pub name: Option<String>,
+	/// The path as it is written Eg. `["a", "b"]`
+	pub path: Vec<String>,
+	/// The import is re-exported with `pub use`
+	pub public: bool,
+	/// The import is an `extern crate`, the ones in the crate root can be used in every module of the crate
+	#[serde(default)]
+	pub extern_crate: bool,
+}
+
+/// The items and imports of a module
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ModuleScope {
+	/// The canonical path of the module Eg. "app::routes"
+	pub module: String,
+	/// The names of the items that are defined in the module
+	pub items: Vec<String>,
+	pub imports: Vec<Import>,
+}
+
+impl ModuleScope {
+	pub fn new(module: &str) -> ModuleScope {
+		ModuleScope {module: module.to_string(), items: Vec::new(), imports: Vec::new()}
+	}
+
+	/// Add an item of the module, `use` and `extern crate` items are added to the imports
+	pub fn add_item(&mut self, item: &Item) {
+		match item {
+			Item::Use(item) => flatten(&item.tree, &mut Vec::new(), !matches!(item.vis, Visibility::Inherited), &mut self.imports),
+			Item::ExternCrate(item) => {
+				let name = item.rename.as_ref().map(|(_, rename)| rename).unwrap_or(&item.ident).to_string();
+				self.imports.push(Import {name: Some(name), path: vec![item.ident.to_string()], public: !matches!(item.vis, Visibility::Inherited), extern_crate: true});
+			},
+			item => {
+				if let Some(name) = item_name(item) {
+					self.items.push(name);
+				}
+			},
+		}
+	}
+}
+
+/// Get the name an item defines in the namespace of its module, None for items without a name
+pub fn item_name(item: &Item) -> Option<String> {
+	let ident = match item {
+		Item::Const(item) => &item.ident,
+		Item::Enum(item) => &item.ident,
+		Item::Fn(item) => &item.sig.ident,
+		Item::Mod(item) => &item.ident,
+		Item::Static(item) => &item.ident,
+		Item::Struct(item) => &item.ident,
+		Item::Trait(item) => &item.ident,
+		Item::TraitAlias(item) => &item.ident,
+		Item::Type(item) => &item.ident,
+		Item::Union(item) => &item.ident,
+		_ => return None,
+	};
+	Some(ident.to_string())
+}
+
+/// Flatten a use tree into its imports Eg. `use a::{b, c::*}` is `a::b` and the glob `a::c`
+pub fn flatten(tree: &UseTree, prefix: &mut Vec<String>, public: bool, imports: &mut Vec<Import>) {
+	match tree {
+		UseTree::Path(path) => {
+			prefix.push(path.ident.to_string());
+			flatten(&path.tree, prefix, public, imports);
+			prefix.pop();
+		},
+		UseTree::Group(group) => {
+			for tree in &group.items {
+				flatten(tree, prefix, public, imports);
+			}
+		},
+		UseTree::Name(name) => imports.push(import(prefix, &name.ident, &name.ident, public)),
+		UseTree::Rename(rename) => imports.push(import(prefix, &rename.ident, &rename.rename, public)),
+		UseTree::Glob(_) => imports.push(Import {name: None, path: prefix.clone(), public, extern_crate: false}),
+	}
+}
+
+/// Create the import of a name, `self` in a group imports the path itself Eg. `use a::b::{self}` imports `b`
+fn import(prefix: &[String], ident: &syn::Ident, name: &syn::Ident, public: bool) -> Import {
+	let mut path = prefix.to_vec();
+	let name = if ident == "self" {
+		if name == "self" { prefix.last().cloned().unwrap_or_default() } else { name.to_string() }
+	} else {
+		path.push(ident.to_string());
+		name.to_string()
+	};
+	Import {name: Some(name), path, public, extern_crate: false}
+}
+
+/// A local variable or a parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+	pub name: String,
+	/// The type of the local when it is known Eg. the type of a parameter
+	pub ty: Option<TypeRef>,
+}
+
+/// The items, imports and locals of a block, they can only be used inside of the block
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockScope {
+	/// The names of the items of the block with their paths Eg. `("helper", "crate::routes::index::helper")`
+	pub items: Vec<(String, String)>,
+	pub imports: Vec<Import>,
+	/// The locals in the order they're bound, a local shadows the ones before it with the same name
+	pub locals: Vec<Local>,
+}
+
+impl BlockScope {
+	/// Create the scope of the items of a block, their paths start with the prefix Eg. "crate::routes::index"
+	pub fn new<'a>(items: impl Iterator<Item = &'a Item>, prefix: &str) -> BlockScope {
+		let mut module = ModuleScope::new(prefix);
+		for item in items {
+			module.add_item(item);
+		}
+		BlockScope {
+			items: module.items.into_iter().map(|name| { let path = format!("{}::{}", prefix, name); (name, path) }).collect(),
+			imports: module.imports,
+			locals: Vec::new(),
+		}
+	}
+}
+
+/// Get the canonical path of a module of a crate Eg. `["crate", "routes"]` of "app" is "app::routes"
+pub fn module_path(krate: &str, module: &[String]) -> String {
+	std::iter::once(krate).chain(module.iter().skip(1).map(String::as_str)).collect::<Vec<_>>().join("::")
+}
+
+/// The import tables of every module of the workspace by the canonical path of the module
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Scopes {
+	pub modules: BTreeMap<String, ModuleScope>,
+}
+
+impl Scopes {
+	/// Add the scope of a module, a module that is part of several crates gets the items and imports of every one
+	pub fn insert(&mut self, scope: ModuleScope) {
+		match self.modules.get_mut(&scope.module) {
+			Some(existing) => {
+				existing.items.extend(scope.items);
+				existing.imports.extend(scope.imports);
+			},
+			None => { self.modules.insert(scope.module.clone(), scope); },
+		}
+	}
+
+	/// Resolve a path that is written in the module to the canonical path of the item it names
+	/// Paths that start with a crate outside of the workspace are kept as they are, None is returned for names that aren't
+	/// defined or imported Eg. local variables.
+	pub fn resolve(&self, module: &str, segments: &[String]) -> Option<String> {
+		self.resolve_path(module, segments, 0, &mut Lookups::new())
+	}
+
+	fn resolve_path(&self, module: &str, segments: &[String], depth: usize, lookups: &mut Lookups) -> Option<String> {
+		let (first, rest) = segments.split_first()?;
+		if depth > MAX_IMPORTS {
+			return None;
+		}
+
+		let mut path = match first.as_str() {
+			"crate" => module.split("::").next()?.to_string(),
+			"self" => module.to_string(),
+			"super" => parent(module)?.to_string(),
+			name => match self.lookup(module, name, module, depth, lookups).or_else(|| self.extern_crate(module, name)) {
+				Some(path) => path,
+				// Workspace crates, the prelude and then crates outside of the workspace
+				None if self.modules.contains_key(name) && !name.contains("::") => name.to_string(),
+				None => match PRELUDE.iter().find(|(prelude, _)| *prelude == name) {
+					Some((_, path)) => path.to_string(),
+					None if !rest.is_empty() => name.to_string(),
+					None => return None,
+				},
+			},
+		};
+
+		for (i, segment) in rest.iter().enumerate() {
+			// `super::super::x`
+			if segment == "super" && rest[..i].iter().all(|previous| previous == "super") {
+				path = parent(&path)?.to_string();
+				continue;
+			}
+			path = match self.modules.contains_key(&path) {
+				true => self.lookup(&path, segment, module, depth, lookups).unwrap_or_else(|| format!("{}::{}", path, segment)),
+				false => format!("{}::{}", path, segment),
+			};
+		}
+		Some(path)
+	}
+
+	/// Resolve the paths of a type that is written in the module to the canonical paths of the types Eg. "crate::api::User"
+	/// of `User` that is imported in the module is "app::models::User", paths that can't be resolved are kept. The traits
+	/// of trait objects and bounds are resolved as well.
+	pub fn resolve_type(&self, module: &str, ty: &TypeRef) -> TypeRef {
+		match ty {
+			TypeRef::Concrete(path) => {
+				let segments: Vec<String> = path.split("::").map(String::from).collect();
+				TypeRef::Concrete(self.resolve(module, &segments).unwrap_or_else(|| path.clone()))
+			},
+			TypeRef::Dyn(traits) => TypeRef::Dyn(traits.iter().map(|name| self.resolve_trait(module, name)).collect()),
+			TypeRef::Bounded(traits) => TypeRef::Bounded(traits.iter().map(|name| self.resolve_trait(module, name)).collect()),
+			TypeRef::Wrapped(inner) => TypeRef::Wrapped(Box::new(self.resolve_type(module, inner))),
+		}
+	}
+
+	/// Resolve a trait that is written in the module to the canonical path of the trait without its generics
+	/// Eg. `repo::UserRepo<T>` is "app::repo::UserRepo", traits that can't be resolved are kept without their generics
+	pub fn resolve_trait(&self, module: &str, name: &str) -> String {
+		let path = name.split(['<', '(']).next().unwrap_or(name);
+		let segments: Vec<String> = path.split("::").map(String::from).collect();
+		self.resolve(module, &segments).unwrap_or_else(|| path.to_string())
+	}
+
+	/// Get the crate of an `extern crate` in the root of the crate of the module Eg. `extern crate serde_json as json;`
+	fn extern_crate(&self, module: &str, name: &str) -> Option<String> {
+		let root = self.modules.get(module.split("::").next()?)?;
+		let import = root.imports.iter().find(|import| import.extern_crate && import.name.as_deref() == Some(name))?;
+		import.path.first().cloned()
+	}
+
+	/// Look a name up in the items and imports of a module from the module the path is written in, glob imports are
+	/// searched last. Imports without `pub` are only followed from the module itself and the modules inside of it.
+	fn lookup(&self, module: &str, name: &str, from: &str, depth: usize, lookups: &mut Lookups) -> Option<String> {
+		let private = from == module || from.strip_prefix(module).is_some_and(|rest| rest.starts_with("::"));
+		let key = (module.to_string(), name.to_string(), private);
+		if let Some(found) = lookups.get(&key) {
+			return found.clone();
+		}
+		lookups.insert(key.clone(), None);
+
+		let found = self.lookup_scope(module, name, private, depth, lookups);
+		lookups.insert(key, found.clone());
+		found
+	}
+
+	fn lookup_scope(&self, module: &str, name: &str, private: bool, depth: usize, lookups: &mut Lookups) -> Option<String> {
+		let scope = self.modules.get(module)?;
+		if scope.items.iter().any(|item| item == name) {
+			return Some(format!("{}::{}", module, name));
+		}
+		let imports = scope.imports.iter().filter(|import| private || import.public);
+		if let Some(import) = imports.clone().find(|import| import.name.as_deref() == Some(name)) {
+			return self.resolve_path(module, &import.path, depth + 1, lookups);
+		}
+		for import in imports.filter(|import| import.name.is_none()) {
+			let found = self.resolve_path(module, &import.path, depth + 1, lookups).and_then(|glob| self.lookup(&glob, name, module, depth + 1, lookups));
+			if found.is_some() {
+				return found;
+			}
+		}
+		None
+	}
+}
+
+/// Get the parent of a module Eg. "app::routes" is "app"
+fn parent(module: &str) -> Option<&str> {
+	module.rsplit_once("::").map(|(parent, _)| parent)
+}
@@ -1,52 +1,229 @@
 /*-------------
 /state.rs
 
-This file handles the state of the application and orchestrates the graph and stack operations.
+This file handles the state of the file that is being visited and orchestrates the graph and stack operations. Every
+file gets a state of its own so the files can be visited in parallel.
 -------------*/
+use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::fmt::Debug;
-use crate::config::APP;
+use std::sync::Arc;
+use crate::analysis::{Function, Unsupported};
+use crate::cfg::Cfg;
+use crate::config::Options;
+use crate::error::Error;
 use crate::graph::Graph;
-use crate::stack::Stack;
+use crate::graph::node::Dispatch;
+use crate::index::{Index, Lookup, TypeRef};
+use crate::log::Log;
+use crate::module::ModuleFile;
+use crate::scope::{self, BlockScope, Local};
 
 #[derive(Debug)]
 pub struct State {
 	pub graph: Graph,
-	pub workspace_libs: Vec<String>,
 	pub current_file: String,
-	pub current_libs: Vec<String>,
-	pub use_stack: Stack,
+	pub current_crate: String,
+	pub current_module: Vec<String>,
+	/// The type and the trait of the impl block that is being visited
+	pub current_impl: Option<(String, Option<String>)>,
+	/// The trait whose default methods are being visited
+	pub current_trait: Option<String>,
+	/// The traits that bound the generic parameters of the impl block or trait that is being visited
+	pub current_bounds: Vec<(String, Vec<String>)>,
+	/// The path of the function that is being visited, the items in its body are under it Eg. "crate::routes::index"
+	pub current_function: Option<String>,
+	/// The items, imports and locals of the blocks the visitor is in, the innermost block is last
+	/// The parameters of the function that is being visited are in a scope of their own before its body.
+	pub block_scopes: Vec<BlockScope>,
+	/// The cfg conditions of the item that is being visited and the items around it
+	pub current_cfg: Vec<String>,
+	/// The cfg options of the crate, items that don't match them are skipped
+	pub cfg: Cfg,
+	/// The index of the workspace
+	pub index: Arc<Index>,
+	/// The lookups in the index, the type of an expression is inferred without a mutable state
+	pub lookups: RefCell<BTreeSet<Lookup>>,
+	/// How many macro expansions the visitor is in
+	pub macro_depth: usize,
+	/// The line of the macro invocation that is being expanded, the tokens of the expansion don't have lines in the file
+	pub expansion_line: Option<usize>,
+	/// Errors that happened while visiting the file
+	pub diagnostics: Vec<Error>,
+	/// Constructs that were encountered but not interpreted
+	pub unsupported: Vec<Unsupported>,
+	pub functions: Vec<Function>,
+	pub options: Options,
+	pub log: Log,
 }
 
 impl State {
-	/// Create the state of the application
-	pub fn new() -> State {
+	/// Create the state for visiting the file, what the file knows about the other files comes from the index
+	pub fn new(options: &Options, file: &ModuleFile, cfg: Cfg, index: Arc<Index>) -> State {
+		let log = Log::new(options);
 		State {
-			graph: Graph::new(),
-			workspace_libs: Vec::new(),
-			current_libs: Vec::new(),
-			current_file: String::new(),
-			use_stack: Stack::new(),
+			graph: Graph::new(log),
+			current_file: file.path.clone(),
+			current_crate: file.krate.clone(),
+			current_module: file.module.clone(),
+			current_impl: None,
+			current_trait: None,
+			current_bounds: Vec::new(),
+			current_function: None,
+			block_scopes: Vec::new(),
+			current_cfg: file.cfg.clone(),
+			cfg,
+			index,
+			lookups: RefCell::new(BTreeSet::new()),
+			macro_depth: 0,
+			expansion_line: None,
+			diagnostics: Vec::new(),
+			unsupported: Vec::new(),
+			functions: Vec::new(),
+			options: options.clone(),
+			log,
 		}
 	}
 
-	/// Adds a new library that is found within the current file
-	pub fn add_new_lib(&mut self, lib: &str) {
-		self.current_libs.push(self.use_stack.get_with_last(&lib));
+	/// Keep a lookup in the index, the result of the file depends on its answer
+	pub fn record(&self, lookup: Lookup) {
+		self.lookups.borrow_mut().insert(lookup);
 	}
 
-	/// Clears the libraries
-	pub fn clear_libs(&mut self) {
-		self.current_libs.clear();
+	/// Resolve the paths of a type that is written in the module we're in to the canonical paths of the types
+	pub fn resolve_type(&self, ty: &TypeRef) -> TypeRef {
+		let module = scope::module_path(&self.current_crate, &self.current_module);
+		self.record(Lookup::Type(module.clone(), ty.clone()));
+		self.index.scopes.resolve_type(&module, ty)
 	}
 
-	/// Adds a new library to the workspace Vector
-	pub fn add_workspace_lib(&mut self, lib: String) {
-		self.workspace_libs.push(lib);
+	/// Resolve a method call on a receiver with the index
+	pub fn resolve_method(&self, receiver: &TypeRef, method: &str) -> Option<(Dispatch, Vec<String>)> {
+		let receiver = self.resolve_type(receiver);
+		self.record(Lookup::Method(receiver.clone(), method.to_string()));
+		self.index.resolve_method(&receiver, method)
 	}
 
-	/// Updates the current file to the file were parsing
-	pub fn update_current_file(&mut self, file: String) {
-		let file = file.replace(&APP.path, "").replace(".rs", "").replace("/src/", "").replace("/", "").replace("mod", "");
-		self.current_file = file;
+	/// Get the type a function or a method returns by its canonical path with the index
+	pub fn return_type(&self, key: &str) -> Option<TypeRef> {
+		self.record(Lookup::ReturnType(key.to_string()));
+		self.index.return_type(key).cloned()
 	}
-}
\ No newline at end of file
+
+	/// Resolve a path that is written in the module we're in to the canonical path of the item it names with the index
+	pub fn resolve_path(&self, segments: &[String]) -> Option<String> {
+		let module = scope::module_path(&self.current_crate, &self.current_module);
+		self.record(Lookup::Path(module.clone(), segments.to_vec()));
+		self.index.scopes.resolve(&module, segments)
+	}
+
+	/// Get the type of a field of a type with the index
+	pub fn field_type(&self, self_type: &str, field: &str) -> Option<TypeRef> {
+		let self_type = match self.resolve_type(&TypeRef::Concrete(self_type.to_string())) {
+			TypeRef::Concrete(self_type) => self_type,
+			_ => return None,
+		};
+		self.record(Lookup::Field(self_type.clone(), field.to_string()));
+		self.index.field_type(&self_type, field)
+	}
+
+	/// Get the path of an item as it is written in the module we're in, `Self` is replaced by the type of the impl block
+	/// and names of the items and imports of the blocks we're in are replaced by their paths. The path is resolved to the
+	/// canonical path of the item once every file is visited.
+	pub fn item_path(&self, segments: &[String]) -> String {
+		match (segments.split_first(), &self.current_impl) {
+			(Some((first, rest)), Some((self_type, _))) if first == "Self" => std::iter::once(self_type.clone()).chain(rest.iter().cloned()).collect::<Vec<_>>().join("::"),
+			_ => self.block_path(segments, self.block_scopes.len()).join("::"),
+		}
+	}
+
+	/// Replace the first segment by the item or import of the innermost of the blocks that has it
+	/// Imports are relative to the blocks around the block they're in.
+	fn block_path(&self, segments: &[String], blocks: usize) -> Vec<String> {
+		let (first, rest) = match segments.split_first() {
+			Some(split) => split,
+			None => return Vec::new(),
+		};
+		for (i, scope) in self.block_scopes[..blocks].iter().enumerate().rev() {
+			if let Some((_, path)) = scope.items.iter().find(|(name, _)| name == first) {
+				return std::iter::once(path.clone()).chain(rest.iter().cloned()).collect();
+			}
+			if let Some(import) = scope.imports.iter().find(|import| import.name.as_ref() == Some(first)) {
+				let path: Vec<String> = import.path.iter().chain(rest.iter()).cloned().collect();
+				return self.block_path(&path, i);
+			}
+		}
+		segments.to_vec()
+	}
+
+	/// Bind locals in the innermost scope, they shadow the locals before them with the same name
+	pub fn bind(&mut self, locals: impl IntoIterator<Item = Local>) {
+		match self.block_scopes.last_mut() {
+			Some(scope) => scope.locals.extend(locals),
+			None => self.log.warn(&"Failed to get the scope to bind the locals in".to_string()),
+		}
+	}
+
+	/// Get the local a name refers to, an item of a block that is inside of the scope of the local hides it
+	pub fn get_local(&self, name: &str) -> Option<&Local> {
+		for scope in self.block_scopes.iter().rev() {
+			if let Some(local) = scope.locals.iter().rev().find(|local| local.name == name) {
+				return Some(local);
+			}
+			if scope.items.iter().any(|(item, _)| item == name) {
+				return None;
+			}
+		}
+		None
+	}
+
+	/// Get the path that the items of the function or the module we're in are under Eg. "crate::routes::index"
+	pub fn item_prefix(&self) -> String {
+		self.current_function.clone().unwrap_or_else(|| self.module_path())
+	}
+
+	/// Starts a new graph for the function that is about to be visited
+	pub fn start_function(&mut self) {
+		self.graph = Graph::new(self.log);
+	}
+
+	/// Finishes the function that was visited and stores it with its graph, methods get the impl block or trait they're in
+	pub fn finish_function(&mut self, key: String, receiver: Option<String>) {
+		let graph = std::mem::replace(&mut self.graph, Graph::new(self.log));
+		let (self_type, trait_name) = match &self.current_impl {
+			Some((self_type, trait_name)) => (Some(self_type.clone()), trait_name.clone()),
+			None => (None, self.current_trait.clone()),
+		};
+		self.functions.push(Function {
+			key,
+			krate: self.current_crate.clone(),
+			module: scope::module_path(&self.current_crate, &self.current_module),
+			file: self.current_file.clone(),
+			cfg: self.current_cfg.clone(),
+			self_type,
+			trait_name,
+			receiver,
+			graph,
+		});
+	}
+
+	/// Get what is known about `Self`, the type of an impl block or any type that implements the trait
+	pub fn self_type(&self) -> Option<TypeRef> {
+		match (&self.current_impl, &self.current_trait) {
+			(Some((self_type, _)), _) => Some(TypeRef::Concrete(self_type.clone())),
+			(None, Some(trait_name)) => Some(TypeRef::Bounded(vec![trait_name.clone()])),
+			(None, None) => None,
+		}
+	}
+
+	/// Add a construct that wasn't interpreted at the line of the span
+	pub fn add_unsupported(&mut self, construct: &str, span: proc_macro2::Span) {
+		let line = self.expansion_line.unwrap_or(span.start().line);
+		self.unsupported.push(Unsupported {construct: construct.to_string(), file: self.current_file.clone(), line});
+	}
+
+	/// Get the path of the module we're in Eg. "crate::routes"
+	pub fn module_path(&self) -> String {
+		self.current_module.join("::")
+	}
+}
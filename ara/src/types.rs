@@ -0,0 +1,246 @@
+/*-------------
+/types.rs
+
+This file is for the type registry of the workspace. Every struct, enum, union and `type` alias is recorded by its fully
+qualified path with its generics, fields and variants while the files are indexed. Type aliases can be resolved to the
+type they stand for with the generic arguments filled in.
+-------------*/
+use std::collections::BTreeMap;
+use proc_macro2::{Group, TokenStream, TokenTree};
+use quote::ToTokens;
+use serde_derive::{Deserialize, Serialize};
+use syn::{Attribute, Fields, GenericArgument, Generics, Item, PathArguments, Type, Visibility};
+use crate::parse::utils;
+
+/// The most aliases that are followed when resolving a type, aliases that refer to themselves stop here
+const MAX_ALIASES: usize = 16;
+
+/// What kind of type a definition is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TypeKind {
+	Struct,
+	Enum,
+	Union,
+	Alias,
+}
+
+/// A data type that is defined in the workspace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TypeDefinition {
+	/// The fully qualified path of the type Eg. "app::models::User"
+	pub path: String,
+	pub name: String,
+	pub kind: TypeKind,
+	pub krate: String,
+	pub file: String,
+	/// The generic parameters Eg. `["'a", "T: Serialize"]`
+	pub generics: Vec<String>,
+	/// The visibility as it is written Eg. "pub(crate)", empty when the type is private
+	pub visibility: String,
+	pub doc: Vec<String>,
+	/// The attributes other than the doc comments Eg. `["derive(Debug, Serialize)"]`
+	pub attrs: Vec<String>,
+	/// The fields of a struct or a union, tuple fields are named by their position
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub fields: Vec<Field>,
+	/// The variants of an enum
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub variants: Vec<Variant>,
+	/// The type that an alias stands for Eg. "std::result::Result<T, AppError>"
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub aliased: Option<String>,
+}
+
+/// A field of a struct, a union or an enum variant
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Field {
+	/// The name of the field, or its position in a tuple Eg. "0"
+	pub name: String,
+	/// The type of the field Eg. "Option<String>"
+	pub ty: String,
+	pub visibility: String,
+	pub doc: Vec<String>,
+	pub attrs: Vec<String>,
+}
+
+/// A variant of an enum
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Variant {
+	pub name: String,
+	pub fields: Vec<Field>,
+	/// The explicit discriminant Eg. "1"
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub discriminant: Option<String>,
+	pub doc: Vec<String>,
+	pub attrs: Vec<String>,
+}
+
+/// The types of the workspace by their fully qualified path
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TypeRegistry {
+	pub types: BTreeMap<String, TypeDefinition>,
+}
+
+impl TypeRegistry {
+	pub fn new() -> TypeRegistry {
+		TypeRegistry {types: BTreeMap::new()}
+	}
+
+	/// Add a type, a type with the same path replaces the one before it
+	pub fn insert(&mut self, definition: TypeDefinition) {
+		self.types.insert(definition.path.clone(), definition);
+	}
+
+	/// Get a type by its fully qualified path Eg. "app::models::User"
+	pub fn get(&self, path: &str) -> Option<&TypeDefinition> {
+		self.types.get(path)
+	}
+
+	/// Resolve the aliases of a fully qualified type Eg. "app::error::Result<User>" is "std::result::Result<User, AppError>"
+	/// Types that aren't aliases are returned as they are, None is returned when the type can't be parsed.
+	pub fn resolve(&self, ty: &str) -> Option<String> {
+		let mut ty: Type = syn::parse_str(ty).ok()?;
+		let mut module = None;
+		for _ in 0..MAX_ALIASES {
+			let (definition, arguments) = match self.alias(&ty, module) {
+				Some(alias) => alias,
+				None => break,
+			};
+			// Paths in the aliased type are relative to the module of the alias
+			module = definition.path.rsplit_once("::").map(|(module, _)| module);
+			let aliased: Type = syn::parse_str(definition.aliased.as_deref()?).ok()?;
+
+			// The generic parameters of the alias are replaced by the arguments in the aliased type
+			let parameters = definition.generics.iter().map(|parameter| parameter.split(':').next().unwrap_or(parameter).trim().to_string());
+			let substitutions: Vec<(String, TokenStream)> = parameters.zip(arguments).collect();
+			ty = syn::parse2(substitute(aliased.into_token_stream(), &substitutions)).ok()?;
+		}
+		Some(utils::get_tokens(&ty))
+	}
+
+	/// Get the alias a type refers to with its generic arguments, the path is looked up in the module as well
+	fn alias(&self, ty: &Type, module: Option<&str>) -> Option<(&TypeDefinition, Vec<TokenStream>)> {
+		let path = match ty {
+			Type::Path(path) if path.qself.is_none() => &path.path,
+			_ => return None,
+		};
+		let key = path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<_>>().join("::");
+		let definition = self.get(&key)
+			.or_else(|| self.get(&format!("{}::{}", module?, key)))
+			.filter(|definition| definition.kind == TypeKind::Alias)?;
+
+		let arguments = match &path.segments.last()?.arguments {
+			PathArguments::AngleBracketed(arguments) => arguments.args.iter().filter_map(|argument| match argument {
+				GenericArgument::Type(ty) => Some(ty.to_token_stream()),
+				GenericArgument::Lifetime(lifetime) => Some(lifetime.to_token_stream()),
+				GenericArgument::Const(expr) => Some(expr.to_token_stream()),
+				_ => None,
+			}).collect(),
+			_ => Vec::new(),
+		};
+		Some((definition, arguments))
+	}
+}
+
+/// Replace the identifiers and lifetimes of the substitutions in the tokens
+fn substitute(tokens: TokenStream, substitutions: &[(String, TokenStream)]) -> TokenStream {
+	let mut result = TokenStream::new();
+	let mut tokens = tokens.into_iter().peekable();
+	while let Some(token) = tokens.next() {
+		let name = match &token {
+			// Lifetimes are a quote followed by an identifier
+			TokenTree::Punct(punct) if punct.as_char() == '\'' => match tokens.peek() {
+				Some(TokenTree::Ident(ident)) => Some(format!("'{}", ident)),
+				_ => None,
+			},
+			TokenTree::Ident(ident) => Some(ident.to_string()),
+			_ => None,
+		};
+
+		match name.and_then(|name| substitutions.iter().find(|(parameter, _)| *parameter == name)) {
+			Some((parameter, replacement)) => {
+				if parameter.starts_with('\'') {
+					tokens.next();
+				}
+				result.extend(replacement.clone());
+			},
+			None => match token {
+				TokenTree::Group(group) => {
+					let mut replaced = Group::new(group.delimiter(), substitute(group.stream(), substitutions));
+					replaced.set_span(group.span());
+					result.extend([TokenTree::Group(replaced)]);
+				},
+				token => result.extend([token]),
+			},
+		}
+	}
+	result
+}
+
+/// Get the definition of a data type item, None is returned for the other items
+/// The module is the path of the module the item is in with the name of the crate Eg. "app::models".
+pub fn type_definition(item: &Item, krate: &str, module: &str, file: &str) -> Option<TypeDefinition> {
+	let (ident, kind, generics, vis, attrs) = match item {
+		Item::Struct(item) => (&item.ident, TypeKind::Struct, &item.generics, &item.vis, &item.attrs),
+		Item::Enum(item) => (&item.ident, TypeKind::Enum, &item.generics, &item.vis, &item.attrs),
+		Item::Union(item) => (&item.ident, TypeKind::Union, &item.generics, &item.vis, &item.attrs),
+		Item::Type(item) => (&item.ident, TypeKind::Alias, &item.generics, &item.vis, &item.attrs),
+		_ => return None,
+	};
+
+	let mut definition = TypeDefinition {
+		path: format!("{}::{}", module, ident),
+		name: ident.to_string(),
+		kind,
+		krate: krate.to_string(),
+		file: file.to_string(),
+		generics: get_generics(generics),
+		visibility: get_visibility(vis),
+		doc: utils::get_doc_comments(attrs),
+		attrs: get_attrs(attrs),
+		fields: Vec::new(),
+		variants: Vec::new(),
+		aliased: None,
+	};
+	match item {
+		Item::Struct(item) => definition.fields = get_fields(&item.fields),
+		Item::Union(item) => definition.fields = get_fields(&Fields::Named(item.fields.clone())),
+		Item::Enum(item) => definition.variants = item.variants.iter().map(|variant| Variant {
+			name: variant.ident.to_string(),
+			fields: get_fields(&variant.fields),
+			discriminant: variant.discriminant.as_ref().map(|(_, expr)| utils::get_tokens(expr)),
+			doc: utils::get_doc_comments(&variant.attrs),
+			attrs: get_attrs(&variant.attrs),
+		}).collect(),
+		Item::Type(item) => definition.aliased = Some(utils::get_tokens(&item.ty)),
+		_ => {},
+	}
+	Some(definition)
+}
+
+/// Get the fields, tuple fields are named by their position
+fn get_fields(fields: &Fields) -> Vec<Field> {
+	fields.iter().enumerate().map(|(position, field)| Field {
+		name: field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or_else(|| position.to_string()),
+		ty: utils::get_tokens(&field.ty),
+		visibility: get_visibility(&field.vis),
+		doc: utils::get_doc_comments(&field.attrs),
+		attrs: get_attrs(&field.attrs),
+	}).collect()
+}
+
+fn get_generics(generics: &Generics) -> Vec<String> {
+	generics.params.iter().map(utils::get_tokens).collect()
+}
+
+fn get_visibility(vis: &Visibility) -> String {
+	match vis {
+		Visibility::Inherited => String::new(),
+		vis => utils::get_tokens(vis),
+	}
+}
+
+/// Get the attributes without the doc comments
+fn get_attrs(attrs: &[Attribute]) -> Vec<String> {
+	attrs.iter().filter(|attr| !attr.path().is_ident("doc")).map(|attr| utils::get_tokens(&attr.meta)).collect()
+}
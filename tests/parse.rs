@@ -1,10 +1,964 @@
 
 #[cfg(test)]
 mod test_parser {
-	use super::*;
+	use ara::{Analysis, Args, Changes, Error, Format, Framework, MemorySource, Options, Output};
+	use ara::file::Filter;
+	use ara::input::project::ProjectConfig;
+	use ara::input::toml::{Dependency, Target, TargetKind};
+	use ara::types::TypeKind;
+	use ara::graph::{edge::Edge, node::{Dispatch, NodeType}};
+	use std::collections::BTreeMap;
+	use std::fs;
 
-	/// Test that we can't register with a validation fail with a bad email address.
-	async fn validate_function_args() {
-		
+	const MANIFEST: &str = "[package]\nname = \"app\"\n";
+
+	/// Analyze the files in memory with the default options
+	fn analyze_files(files: &[(&str, &str)]) -> Analysis {
+		let files: BTreeMap<String, String> = files.iter().map(|(path, content)| (path.to_string(), content.to_string())).collect();
+		ara::analyze_memory(Options::default(), MANIFEST, files).unwrap()
+	}
+
+	/// Path to the axum example project
+	fn example_path() -> String {
+		format!("{}/../examples/axum", env!("CARGO_MANIFEST_DIR"))
+	}
+
+	/// Test that the library can be run with explicit options instead of the command line arguments.
+	#[test]
+	fn analyze_with_explicit_options() {
+		let analysis = ara::analyze(Options::new(&example_path())).unwrap();
+		assert_eq!(analysis.members, vec![".".to_string()]);
+		assert_eq!(analysis.packages[0].name, "axum");
+		assert_eq!(analysis.packages[0].targets[0].path, "src/main.rs");
+		assert!(analysis.parsed.iter().any(|file| file.ends_with("main.rs")));
+		assert!(analysis.get_function("axum::main").is_some());
+		assert!(analysis.get_function("axum::routes::create_routes").is_some());
+	}
+
+	/// Test that several analyses with different settings can run in the same process.
+	#[test]
+	fn analyze_with_function_filter() {
+		let mut options = Options::new(&example_path());
+		options.function_name = vec!["get_health".to_string()];
+		let filtered = ara::analyze(options).unwrap();
+		assert_eq!(filtered.functions.len(), 1);
+		assert_eq!(filtered.functions[0].key, "crate::routes::get_health");
+
+		let all = ara::analyze(Options::new(&example_path())).unwrap();
+		assert_eq!(all.functions.len(), 3);
+	}
+
+	/// Test that every function gets a graph of its own.
+	#[test]
+	fn function_graphs_are_separate() {
+		let analysis = ara::analyze(Options::new(&example_path())).unwrap();
+		let function = analysis.get_function("axum::routes::get_health").unwrap();
+		assert_eq!(function.graph.nodes[0].node_type, NodeType::Function);
+		assert_eq!(function.graph.nodes[0].doc.len(), 2);
+		assert!(function.graph.nodes.iter().any(|node| node.key == "into_response" && node.node_type == NodeType::Method));
+		assert!(!function.graph.nodes.iter().any(|node| node.key == "crate::main"));
+	}
+
+	/// Test that a missing manifest is returned as an error instead of exiting.
+	#[test]
+	fn missing_manifest_is_an_error() {
+		let path = format!("{}/missing_manifest", env!("CARGO_TARGET_TMPDIR"));
+		fs::create_dir_all(&path).unwrap();
+
+		let result = ara::analyze(Options::new(&path));
+		assert_eq!(result.unwrap_err(), Error::ManifestNotFound { path: format!("{}/Cargo.toml", path) });
+	}
+
+	/// Test that a file that can't be parsed is added to the diagnostics and the other files are still analyzed.
+	#[test]
+	fn broken_file_is_a_diagnostic() {
+		let analysis = analyze_files(&[
+			("src/main.rs", "mod broken;\n\nfn main() {\n\thelper();\n}\n"),
+			("src/broken.rs", "fn broken( {\n"),
+		]);
+
+		assert_eq!(analysis.functions.len(), 1);
+		assert_eq!(analysis.parsed, vec!["src/main.rs".to_string()]);
+		assert_eq!(analysis.skipped, vec!["src/broken.rs".to_string()]);
+		assert!(matches!(&analysis.diagnostics[..], [Error::UnsupportedSyntax { path, span, .. }] if path == "src/broken.rs" && span.line == 1));
+	}
+
+	/// Test that a call in the initializer of a constant outside of a function doesn't take the analysis down.
+	#[test]
+	fn const_initializer_with_a_call() {
+		let analysis = analyze_files(&[
+			("src/main.rs", "mod limits;\n\nfn main() {\n\trun();\n}\n"),
+			("src/limits.rs", "const MAX: u32 = compute();\n\npub fn compute() -> u32 {\n\tdefault_max()\n}\n"),
+		]);
+
+		assert!(analysis.get_function("app::main").is_some());
+		assert!(analysis.get_function("app::limits::compute").is_some());
+		assert_eq!(analysis.parsed, vec!["src/main.rs".to_string(), "src/limits.rs".to_string()]);
+	}
+
+	/// Test that the arguments of a call are connected to the call.
+	#[test]
+	fn call_arguments_are_connected() {
+		let analysis = analyze_files(&[("src/main.rs", "fn main() {\n\tlet user = load(id);\n\tsave(user);\n}\n")]);
+		let graph = &analysis.get_function("app::main").unwrap().graph;
+
+		let keys: Vec<_> = graph.nodes.iter().map(|node| (node.key.as_str(), node.node_type.clone())).collect();
+		assert_eq!(keys, vec![
+			("crate::main", NodeType::Function),
+			("load", NodeType::Call),
+			("id", NodeType::Call),
+			("save", NodeType::Call),
+			("user", NodeType::Local),
+		]);
+		assert_eq!(graph.nodes[1].local, "user");
+		assert_eq!(graph.edges, vec![Edge::new(0, 1), Edge::new(1, 2), Edge::new(0, 3), Edge::new(3, 4)]);
+	}
+
+	/// Test that a method chain is connected from the receiver through every method.
+	#[test]
+	fn method_chain_is_connected() {
+		let analysis = analyze_files(&[("src/lib.rs", "pub fn health() -> Response {\n\t(StatusCode::OK, \"ok\").into_response()\n}\n")]);
+		let graph = &analysis.get_function("app::health").unwrap().graph;
+
+		let method = graph.nodes.iter().find(|node| node.key == "into_response").unwrap();
+		assert_eq!(method.node_type, NodeType::Method);
+		assert!(graph.nodes.iter().any(|node| node.key == "StatusCode::OK"));
+		assert!(graph.edges.iter().any(|edge| edge.to == method.id));
+	}
+
+	/// Test that the project configuration is read from ara.toml and that the command line arguments win.
+	#[test]
+	fn project_config_is_merged_with_args() {
+		let mut source = MemorySource::new(MANIFEST);
+		source.add_file("ara.toml", "ignore = [\"/generated\"]\nfunction-name = [\"main\"]\nframework = \"axum\"\nformats = [\"json\", \"terminal\"]\n\n[output]\njson = \"target/ara.json\"\n");
+		let config = ProjectConfig::load(&source).unwrap();
+
+		let mut options = Options::default();
+		options.merge_config(config);
+		assert_eq!(options.ignore, vec!["/generated".to_string()]);
+		assert_eq!(options.framework, Some(Framework::Axum));
+		assert_eq!(options.outputs, vec![Output::new(Format::Json, Some("target/ara.json".to_string())), Output::new(Format::Terminal, None)]);
+
+		options.merge_args(Args { function_name: Some(vec!["health".to_string()]), format: Some(vec![Format::Json]), ..Args::default() }).unwrap();
+		assert_eq!(options.ignore, vec!["/generated".to_string()]);
+		assert_eq!(options.function_name, vec!["health".to_string()]);
+		assert_eq!(options.outputs, vec![Output::new(Format::Json, Some("target/ara.json".to_string()))]);
+		assert_eq!(options.path, ".");
+
+		// The formats would overwrite each other in a single output file
+		let output = Some("out.txt".to_string());
+		options.merge_args(Args { format: Some(vec![Format::Json]), output: output.clone(), ..Args::default() }).unwrap();
+		assert_eq!(options.outputs, vec![Output::new(Format::Json, output.clone())]);
+		let result = options.merge_args(Args { format: Some(vec![Format::Terminal, Format::Json]), output, ..Args::default() });
+		assert!(matches!(result, Err(Error::InvalidArgument { argument, .. }) if argument == "--output"));
+
+		// The switches that the configuration turns on can be turned off again
+		let mut source = MemorySource::new(MANIFEST);
+		source.add_file("ara.toml", "all-features = true
+no-default-features = true
+release = true
+gitignore = false
+");
+		let mut options = Options::default();
+		options.merge_config(ProjectConfig::load(&source).unwrap());
+		assert!(options.all_features && options.no_default_features && !options.debug_assertions && !options.gitignore);
+		options.merge_args(Args::default()).unwrap();
+		assert!(options.all_features && options.no_default_features && !options.debug_assertions && !options.gitignore);
+		options.merge_args(Args { no_all_features: true, default_features: true, dev: true, gitignore: true, ..Args::default() }).unwrap();
+		assert!(!options.all_features && !options.no_default_features && options.debug_assertions && options.gitignore);
+	}
+
+	/// Test that the project configuration can be stored in the metadata of the Cargo.toml.
+	#[test]
+	fn project_config_from_package_metadata() {
+		let source = MemorySource::new("[package]\nname = \"app\"\n\n[package.metadata.ara]\nignore = [\"/fixtures\"]\n");
+		let config = ProjectConfig::load(&source).unwrap();
+		assert_eq!(config.ignore, Some(vec!["/fixtures".to_string()]));
+
+		let source = MemorySource::new("[workspace.metadata.ara]\nframework = \"serverless\"\n");
+		assert!(matches!(ProjectConfig::load(&source), Err(Error::ConfigParse { .. })));
+
+		// A mistyped key isn't ignored
+		let source = MemorySource::new("[package]\nname = \"app\"\n\n[package.metadata.ara]\nignores = [\"/fixtures\"]\n");
+		assert!(matches!(ProjectConfig::load(&source), Err(Error::ConfigParse { message, .. }) if message.contains("ignores")));
+		let mut source = MemorySource::new(MANIFEST);
+		source.add_file("ara.toml", "framework = \"axum\"\nformat = [\"json\"]\n");
+		assert!(matches!(ProjectConfig::load(&source), Err(Error::ConfigParse { path, span: Some(span), .. }) if path == "ara.toml" && span.line == 2));
+	}
+
+	/// Test that the analysis can be written out as json.
+	#[test]
+	fn analysis_renders_as_json() {
+		let analysis = analyze_files(&[("src/main.rs", "fn main() {\n\trun();\n}\n")]);
+		let json = ara::output::render(&analysis, Format::Json);
+		assert!(json.contains("\"key\": \"crate::main\""));
+		assert!(json.contains("\"node_type\": \"Call\""));
+	}
+
+	/// Test that ignore patterns are globs relative to the project and that the ignore files are honored.
+	#[test]
+	fn ignore_patterns_and_ignore_files() {
+		let mut source = MemorySource::new(MANIFEST);
+		source.add_file("src/main.rs", "mod contest;\nmod generated { mod api; }\n#[path = \"schema.gen.rs\"] mod schema;\n#[path = \"keep.gen.rs\"] mod keep;\n#[path = \"../tests_util/helper.rs\"] mod helper;\n#[path = \"../vendor/lib.rs\"] mod vendored;\nfn main() {}")
+			.add_file("src/contest.rs", "fn contest() {}")
+			.add_file("src/generated/api.rs", "fn generated() {}")
+			.add_file("src/schema.gen.rs", "fn schema() {}")
+			.add_file("src/keep.gen.rs", "fn keep() {}")
+			.add_file("tests/it.rs", "fn it() {}")
+			.add_file("tests_util/helper.rs", "fn helper() {}")
+			.add_file("vendor/lib.rs", "fn vendored() {}")
+			.add_file(".gitignore", "/vendor\n");
+
+		let ignore = ["/tests", "**/generated/**", "*.gen.rs", "!src/keep.gen.rs"].map(String::from).to_vec();
+		let mut options = Options { ignore, ..Options::default() };
+		let analysis = ara::analyze_source(options.clone(), &source).unwrap();
+		assert_eq!(analysis.parsed, vec!["src/main.rs", "src/contest.rs", "src/keep.gen.rs", "tests_util/helper.rs"]);
+		assert!(analysis.skipped.contains(&"vendor".to_string()));
+
+		options.gitignore = false;
+		options.include = vec!["src/**".to_string()];
+		let analysis = ara::analyze_source(options, &source).unwrap();
+		assert_eq!(analysis.parsed, vec!["src/main.rs", "src/contest.rs", "src/keep.gen.rs"]);
+		assert!(analysis.skipped.contains(&"vendor/lib.rs".to_string()));
+	}
+
+	/// Test that the files are discovered from the crate roots by following the module declarations.
+	#[test]
+	fn modules_are_discovered_from_crate_roots() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod routes;\n#[path = \"generated/schema.rs\"]\nmod schema;\nmod inline {\n\tpub fn nested() {}\n\tmod child;\n}\nmod missing;\n"),
+			("src/routes/mod.rs", "pub mod users;\n"),
+			("src/routes/users.rs", "pub fn list() {}\n"),
+			("src/generated/schema.rs", "mod types;\npub fn schema() {}\n"),
+			("src/generated/types.rs", "pub fn types() {}\n"),
+			("src/inline/child.rs", "pub fn child() {}\n"),
+			("src/bin/worker.rs", "fn main() {}\n"),
+			("src/dead.rs", "pub fn dead() {}\n"),
+			("examples/demo.rs", "fn main() {}\n"),
+		]);
+
+		let mut keys: Vec<_> = analysis.functions.iter().map(|function| function.key.as_str()).collect();
+		keys.sort();
+		assert_eq!(keys, vec![
+			"crate::inline::child::child",
+			"crate::inline::nested",
+			"crate::main",
+			"crate::routes::users::list",
+			"crate::schema::schema",
+			"crate::schema::types::types",
+		]);
+		assert_eq!(analysis.get_function("app::routes::users::list").unwrap().file, "src/routes/users.rs");
+		assert!(analysis.skipped.contains(&"src/dead.rs".to_string()));
+		assert!(analysis.skipped.contains(&"examples/demo.rs".to_string()));
+		assert!(matches!(&analysis.diagnostics[..], [Error::ModuleNotFound { module, span, .. }] if module == "crate::missing" && span.line == 8));
+	}
+
+	/// Test that member globs are expanded and every package manifest is read for its targets and dependencies.
+	#[test]
+	fn workspace_members_and_targets() {
+		let mut source = MemorySource::new("[workspace]\nmembers = [\"crates/*\", \"services/api\"]\nexclude = [\"crates/old\"]\n\n[workspace.dependencies]\nshared-types = { path = \"crates/shared-types\" }\n");
+		source.add_file("crates/shared-types/Cargo.toml", "[package]\nname = \"shared-types\"\n")
+			.add_file("crates/shared-types/src/lib.rs", "pub fn shared() {}\n")
+			.add_file("crates/old/Cargo.toml", "[package]\nname = \"old\"\n")
+			.add_file("crates/old/src/lib.rs", "pub fn old() {}\n")
+			.add_file("crates/notes/README.md", "not a crate")
+			.add_file("services/api/Cargo.toml", "[package]\nname = \"api-server\"\n\n[lib]\nname = \"api\"\npath = \"src/api.rs\"\n\n[[bin]]\nname = \"migrate\"\npath = \"tools/migrate.rs\"\n\n[dependencies]\nshared-types = { workspace = true }\nutils = { path = \"../../crates/utils\", package = \"company-utils\" }\nserde = \"1\"\n")
+			.add_file("services/api/src/api.rs", "pub fn handler() {}\n")
+			.add_file("services/api/src/main.rs", "fn main() {}\n")
+			.add_file("services/api/tools/migrate.rs", "fn main() {}\n");
+
+		let analysis = ara::analyze_source(Options::default(), &source).unwrap();
+		assert_eq!(analysis.members, vec!["crates/shared-types", "services/api"]);
+
+		let api = analysis.packages.iter().find(|package| package.name == "api-server").unwrap();
+		assert_eq!(api.crate_name, "api_server");
+		assert_eq!(api.targets, vec![
+			Target { name: "api".to_string(), crate_name: "api".to_string(), kind: TargetKind::Lib, path: "services/api/src/api.rs".to_string() },
+			Target { name: "migrate".to_string(), crate_name: "migrate".to_string(), kind: TargetKind::Bin, path: "services/api/tools/migrate.rs".to_string() },
+			Target { name: "api-server".to_string(), crate_name: "api_server".to_string(), kind: TargetKind::Bin, path: "services/api/src/main.rs".to_string() },
+		]);
+		assert_eq!(api.dependencies, vec![
+			Dependency { name: "shared-types".to_string(), crate_name: "shared_types".to_string(), package: "shared-types".to_string(), path: "crates/shared-types".to_string(), optional: false, features: Vec::new(), default_features: true },
+			Dependency { name: "utils".to_string(), crate_name: "utils".to_string(), package: "company-utils".to_string(), path: "crates/utils".to_string(), optional: false, features: Vec::new(), default_features: true },
+		]);
+
+		let handler = analysis.get_function("api::handler").unwrap();
+		assert_eq!(handler.krate, "api");
+		assert!(analysis.functions.iter().any(|function| function.key == "crate::shared" && function.krate == "shared_types"));
+		assert!(!analysis.functions.iter().any(|function| function.key == "crate::old"));
+		assert_eq!(analysis.functions.iter().filter(|function| function.key == "crate::main").count(), 2);
+		assert_eq!(analysis.get_function("api_server::main").unwrap().file, "services/api/src/main.rs");
+		assert_eq!(analysis.get_function("migrate::main").unwrap().file, "services/api/tools/migrate.rs");
+	}
+
+	/// Test that a manifest without a package or a workspace is an error, even when it mentions a workspace.
+	#[test]
+	fn manifest_without_package_or_workspace() {
+		let source = MemorySource::new("# not a workspace\n[dependencies]\nserde = { workspace = true }\n");
+		let result = ara::analyze_source(Options::default(), &source);
+		assert_eq!(result.unwrap_err(), Error::NoPackageOrWorkspace { path: "Cargo.toml".to_string() });
+	}
+
+	/// Test that the files analyzed in parallel are merged in the order they were discovered.
+	#[test]
+	fn parallel_results_are_deterministic() {
+		let mut source = MemorySource::new(MANIFEST);
+		let declarations: String = (0..40).map(|i| format!("mod module_{};\n", i)).collect();
+		source.add_file("src/lib.rs", &declarations);
+		for i in 0..40 {
+			source.add_file(&format!("src/module_{}.rs", i), &format!("pub fn first_{i}() {{ call_{i}(); }}\npub fn second_{i}() {{}}\n"));
+		}
+
+		let expected: Vec<String> = (0..40).flat_map(|i| [format!("crate::module_{}::first_{}", i, i), format!("crate::module_{}::second_{}", i, i)]).collect();
+		for _ in 0..3 {
+			let analysis = ara::analyze_source(Options::default(), &source).unwrap();
+			let keys: Vec<String> = analysis.functions.iter().map(|function| function.key.clone()).collect();
+			assert_eq!(keys, expected);
+			assert_eq!(analysis.parsed.len(), 41);
+		}
+	}
+
+	/// Test that unchanged files are loaded from the cache and changed files are visited again.
+	#[test]
+	fn cache_skips_unchanged_files() {
+		let path = format!("{}/cache_project", env!("CARGO_TARGET_TMPDIR"));
+		let _ = fs::remove_dir_all(&path);
+		fs::create_dir_all(format!("{}/src", path)).unwrap();
+		fs::write(format!("{}/Cargo.toml", path), MANIFEST).unwrap();
+		fs::write(format!("{}/src/lib.rs", path), "mod api;\npub fn root() {}\n").unwrap();
+		fs::write(format!("{}/src/api.rs", path), "pub fn handler() {}\n").unwrap();
+
+		let cached = || Options { cache: true, ..Options::new(&path) };
+		let entries = || fs::read_dir(format!("{}/target/ara", path)).unwrap().count();
+		let first = ara::analyze(cached()).unwrap();
+		assert!(first.cached.is_empty());
+		assert!(first.get_function("app::api::handler").is_some());
+
+		let second = ara::analyze(cached()).unwrap();
+		assert_eq!(second.cached, vec!["src/lib.rs", "src/api.rs"]);
+		assert_eq!(second.functions.len(), first.functions.len());
+
+		// Only the changed file is visited again
+		fs::write(format!("{}/src/api.rs", path), "pub fn changed() {}\n").unwrap();
+		let third = ara::analyze(cached()).unwrap();
+		assert_eq!(third.cached, vec!["src/lib.rs"]);
+		assert!(third.get_function("app::api::changed").is_some());
+		assert!(third.get_function("app::api::handler").is_none());
+
+		// The declared modules are looked up again even though the declaring file is cached
+		fs::remove_file(format!("{}/src/api.rs", path)).unwrap();
+		let fourth = ara::analyze(cached()).unwrap();
+		assert_eq!(fourth.cached, vec!["src/lib.rs"]);
+		assert!(matches!(fourth.diagnostics.as_slice(), [Error::ModuleNotFound { module, .. }] if module == "crate::api"));
+
+		// The entries of the files that changed or are gone are pruned, only the index and the result of lib.rs are left
+		assert_eq!(entries(), 2);
+
+		// The cache is only used when it's turned on
+		assert!(ara::analyze(Options::new(&path)).unwrap().cached.is_empty());
+	}
+
+	/// Test that editing a type only visits the files again whose lookups in the index get a different answer.
+	#[test]
+	fn cache_follows_the_lookups_of_files() {
+		let path = format!("{}/cache_lookups", env!("CARGO_TARGET_TMPDIR"));
+		let _ = fs::remove_dir_all(&path);
+		fs::create_dir_all(format!("{}/src", path)).unwrap();
+		fs::write(format!("{}/Cargo.toml", path), MANIFEST).unwrap();
+		fs::write(format!("{}/src/lib.rs", path), "mod models;\nmod api;\nmod other;\n").unwrap();
+		fs::write(format!("{}/src/models.rs", path), "pub struct User { pub name: String }\nimpl User {\n\tpub fn save(&self) {}\n}\n").unwrap();
+		fs::write(format!("{}/src/api.rs", path), "use crate::models::User;\npub fn handler(user: User) { user.save(); }\n").unwrap();
+		fs::write(format!("{}/src/other.rs", path), "pub fn unrelated() { helper(); }\n").unwrap();
+		let cached = || ara::analyze(Options { cache: true, ..Options::new(&path) }).unwrap();
+		let call = |analysis: &Analysis| analysis.get_function("app::api::handler").unwrap().graph.nodes.iter().find(|node| node.key == "save").cloned().unwrap();
+		assert_eq!(call(&cached()).candidates, vec!["app::models::User::save"]);
+
+		// A new field doesn't change where the method call goes
+		fs::write(format!("{}/src/models.rs", path), "pub struct User { pub name: String, pub age: u32 }\nimpl User {\n\tpub fn save(&self) {}\n}\n").unwrap();
+		assert_eq!(cached().cached, vec!["src/lib.rs", "src/api.rs", "src/other.rs"]);
+
+		// Renaming the method does, so only the file that calls it is visited again
+		fs::write(format!("{}/src/models.rs", path), "pub struct User { pub name: String, pub age: u32 }\nimpl User {\n\tpub fn store(&self) {}\n}\n").unwrap();
+		let analysis = cached();
+		assert_eq!(analysis.cached, vec!["src/lib.rs", "src/other.rs"]);
+		assert!(call(&analysis).candidates.is_empty());
+	}
+
+	/// Test that single changed paths are ignored the same way as when the files are grabbed.
+	#[test]
+	fn changed_paths_use_the_ignore_rules() {
+		let mut source = MemorySource::new(MANIFEST);
+		source.add_file(".gitignore", "/vendor\n")
+			.add_file("src/.ignore", "*.gen.rs\n");
+
+		let options = Options { ignore: vec!["/target".to_string(), "!src/keep.gen.rs".to_string()], ..Options::default() };
+		let filter = Filter::new(&options).unwrap();
+		assert!(!filter.is_path_ignored(&source, "src/lib.rs", false));
+		assert!(filter.is_path_ignored(&source, "target/ara/cache.json", false));
+		assert!(filter.is_path_ignored(&source, "vendor/lib.rs", false));
+		assert!(filter.is_path_ignored(&source, "src/schema.gen.rs", false));
+		assert!(!filter.is_path_ignored(&source, "src/keep.gen.rs", false));
+		assert!(filter.is_path_ignored(&source, ".git/index", false));
+
+		// Whitelisted files still have to be included
+		let options = Options { include: vec!["src/lib.rs".to_string()], ..options };
+		let filter = Filter::new(&options).unwrap();
+		assert!(!filter.is_path_ignored(&source, "src/lib.rs", false));
+		assert!(filter.is_path_ignored(&source, "src/keep.gen.rs", false));
+	}
+
+	/// Test that the API changes between two runs are summarized by the path of the functions.
+	#[test]
+	fn changes_between_runs() {
+		let old = analyze_files(&[("src/lib.rs", "pub fn kept() { call(); }\npub fn changed() { first(); }\npub fn removed() {}\n")]);
+		let new = analyze_files(&[("src/lib.rs", "pub fn kept() { call(); }\npub fn changed() { second(); }\npub fn added() {}\n")]);
+
+		let changes = Changes::between(&old, &new);
+		assert_eq!(changes, Changes { added: vec!["app::added".to_string()], removed: vec!["app::removed".to_string()], changed: vec!["app::changed".to_string()] });
+		assert_eq!(changes.to_string(), "1 added, 1 removed, 1 changed\n+ app::added\n- app::removed\n~ app::changed");
+		assert!(Changes::between(&new, &new).is_empty());
+	}
+
+	/// Test that items are only analyzed when their cfg predicate holds for the enabled features and cfg options.
+	#[test]
+	fn cfg_attributes_follow_features() {
+		let manifest = "[package]\nname = \"app\"\n\n[features]\ndefault = [\"sqlite\"]\nsqlite = []\npostgres = [\"pool\"]\npool = []\n\n[dependencies]\nmetrics = { version = \"1\", optional = true }\n";
+		let files = [
+			("src/lib.rs", "#[cfg(feature = \"postgres\")]\nmod postgres;\n#[cfg(test)]\nmod tests;\npub fn always() {}\n#[cfg(feature = \"sqlite\")]\npub fn sqlite() {}\n#[cfg(not(feature = \"sqlite\"))]\npub fn without_sqlite() {}\n#[cfg(any(feature = \"metrics\", test))]\npub fn metrics() {}\npub fn body() {\n\t#[cfg(feature = \"pool\")]\n\tpooled();\n\tplain();\n}\n"),
+			("src/postgres.rs", "pub fn connect() {}\n#[cfg(feature = \"pool\")]\npub fn pooled() {}\n"),
+			("src/tests.rs", "fn helper() {}\n"),
+		];
+		let analyze = |options: Options| {
+			let files: BTreeMap<String, String> = files.iter().map(|(path, content)| (path.to_string(), content.to_string())).collect();
+			ara::analyze_memory(options, manifest, files).unwrap()
+		};
+		let keys = |analysis: &Analysis| analysis.functions.iter().map(|function| function.key.clone()).collect::<Vec<_>>();
+
+		let analysis = analyze(Options::default());
+		assert_eq!(keys(&analysis), vec!["crate::always", "crate::sqlite", "crate::body"]);
+		assert_eq!(analysis.get_function("app::sqlite").unwrap().cfg, vec!["feature = \"sqlite\""]);
+		assert!(analysis.get_function("app::always").unwrap().cfg.is_empty());
+		assert!(!analysis.get_function("app::body").unwrap().graph.nodes.iter().any(|node| node.key == "pooled"));
+		assert!(analysis.skipped.contains(&"src/tests.rs".to_string()));
+
+		let analysis = analyze(Options { features: vec!["postgres".to_string(), "metrics".to_string()], no_default_features: true, ..Options::default() });
+		assert_eq!(keys(&analysis), vec!["crate::always", "crate::without_sqlite", "crate::metrics", "crate::body", "crate::postgres::connect", "crate::postgres::pooled"]);
+		assert_eq!(analysis.get_function("app::postgres::pooled").unwrap().cfg, vec!["feature = \"postgres\"", "feature = \"pool\""]);
+		assert!(analysis.get_function("app::body").unwrap().graph.nodes.iter().any(|node| node.key == "pooled"));
+
+		let analysis = analyze(Options { cfg: vec!["test".to_string()], ..Options::default() });
+		assert!(analysis.get_function("app::tests::helper").is_some());
+		assert_eq!(analysis.get_function("app::metrics").unwrap().cfg, vec!["any(feature = \"metrics\", test)"]);
+
+		let analysis = analyze(Options { all_features: true, ..Options::default() });
+		assert_eq!(analysis.functions.len(), 6);
+	}
+
+	/// Test that `debug_assertions` follows the profile and cfg predicates that can't be parsed are reported once.
+	#[test]
+	fn cfg_profile_and_invalid_predicates() {
+		let analyze = |options: Options| {
+			let files = BTreeMap::from([("src/lib.rs".to_string(), "#[cfg(debug_assertions)]\npub fn debug() {}\n#[cfg(feature = 1)]\npub fn broken() {}\npub fn body() {\n\t#[cfg(unknown(x))]\n\tcall();\n}\n".to_string())]);
+			ara::analyze_memory(options, MANIFEST, files).unwrap()
+		};
+
+		let analysis = analyze(Options::default());
+		assert!(analysis.get_function("app::debug").is_some());
+		assert!(analysis.get_function("app::broken").is_some());
+		let invalid: Vec<(String, usize)> = analysis.diagnostics.iter().filter_map(|diagnostic| match diagnostic {
+			Error::InvalidCfg { predicate, span, .. } => Some((predicate.clone(), span.line)),
+			_ => None,
+		}).collect();
+		assert_eq!(invalid, vec![("cfg (feature = 1)".to_string(), 3), ("cfg (unknown (x))".to_string(), 6)]);
+		assert_eq!(analysis.diagnostics.len(), 2);
+
+		let analysis = analyze(Options { debug_assertions: false, cfg: vec!["not a cfg".to_string()], ..Options::default() });
+		assert!(analysis.get_function("app::debug").is_none());
+		assert!(matches!(&analysis.diagnostics[0], Error::InvalidArgument { argument, .. } if argument == "--cfg"));
+	}
+
+	/// Test that the features a package enables on the other packages of the workspace are enabled for them.
+	#[test]
+	fn features_are_unified_across_the_workspace() {
+		let mut source = MemorySource::new("[workspace]\nmembers = [\"api\", \"store\"]\n");
+		source.add_file("api/Cargo.toml", "[package]\nname = \"api\"\n\n[features]\ndefault = [\"store/cache\"]\nadmin = [\"store?/audit\"]\n\n[dependencies]\nstore = { path = \"../store\", default-features = false, features = [\"json\"] }\n")
+			.add_file("api/src/lib.rs", "pub fn handler() {}\n")
+			.add_file("store/Cargo.toml", "[package]\nname = \"store\"\n\n[features]\ndefault = []\njson = []\ncache = []\naudit = []\n")
+			.add_file("store/src/lib.rs", "#[cfg(feature = \"json\")]\npub fn json() {}\n#[cfg(feature = \"cache\")]\npub fn cache() {}\n#[cfg(feature = \"audit\")]\npub fn audit() {}\n");
+
+		let analysis = ara::analyze_source(Options::default(), &source).unwrap();
+		let store = analysis.packages.iter().find(|package| package.name == "store").unwrap();
+		let api = analysis.packages.iter().find(|package| package.name == "api").unwrap();
+		assert_eq!(api.dependencies[0].features, vec!["json"]);
+		assert!(!api.dependencies[0].default_features);
+		assert_eq!(store.features.len(), 4);
+
+		let features = ara::cfg::features(&analysis.packages, &Options::default());
+		assert_eq!(features["store"].iter().collect::<Vec<_>>(), vec!["cache", "default", "json"]);
+		let keys: Vec<&str> = analysis.functions.iter().map(|function| function.key.as_str()).collect();
+		assert_eq!(keys, vec!["crate::handler", "crate::json", "crate::cache"]);
+
+		let options = Options { features: vec!["api/admin".to_string()], ..Options::default() };
+		assert!(ara::cfg::features(&analysis.packages, &options)["store"].contains("audit"));
+	}
+
+	/// Test that the arguments of built-in macros are visited as calls and that format strings are kept as templates.
+	#[test]
+	fn builtin_macro_arguments_are_visited() {
+		let analysis = analyze_files(&[("src/lib.rs", "pub fn handler() -> String {\n\tprintln!(\"Handling {}\", user_name());\n\tlet items = vec![load(), fetch(1)];\n\tlet zeros = vec![zero(); size()];\n\tlet body = json!({\"id\": make_id(), \"tags\": [tag()], \"ok\": true, [key()]: null});\n\tassert_eq!(count(), 2, \"count was {}\", total());\n\tlet ok = matches!(status(), Status::Ok | Status::Created if check());\n\tcustom!(hidden());\n\tformat!(\"Hello {name}\", name = greet())\n}\n")]);
+		let graph = &analysis.get_function("app::handler").unwrap().graph;
+		let node = |key: &str| graph.nodes.iter().find(|node| node.key == key).unwrap_or_else(|| panic!("missing node {}", key));
+
+		for key in ["user_name", "load", "fetch", "zero", "size", "make_id", "tag", "key", "count", "total", "status", "check", "greet"] {
+			assert_eq!(node(key).node_type, NodeType::Call);
+		}
+		assert_eq!(node("println").literal, Some("Handling {}".to_string()));
+		assert_eq!(node("format").literal, Some("Hello {name}".to_string()));
+		assert_eq!(node("assert_eq").literal, Some("count was {}".to_string()));
+		assert!(graph.edges.contains(&Edge::new(node("println").id, node("user_name").id)));
+		assert!(graph.edges.contains(&Edge::new(node("vec").id, node("fetch").id)));
+
+		// Macros with a syntax of their own stay opaque
+		assert_eq!(node("custom").node_type, NodeType::Macro);
+		assert!(!graph.nodes.iter().any(|node| node.key == "hidden"));
+	}
+
+	/// Test that the macro_rules macros of the workspace are expanded and that failed expansions and definitions are reported.
+	#[test]
+	fn local_macros_are_expanded() {
+		let analysis = analyze_files(&[
+			("src/macros.rs", "#[macro_export]\nmacro_rules! route {\n\t($name:ident => $handler:path) => {\n\t\tpub fn $name() { $handler(); $crate::register(stringify!($name)); }\n\t};\n}\nmacro_rules! call_all {\n\t($($f:ident),* $(,)?) => { $( $f(); )* };\n}\nmacro_rules! double {\n\t($e:expr) => { $e * 2 };\n}\nmacro_rules! recurse {\n\t() => { recurse!(); };\n}\nmacro_rules! only_numbers {\n\t($n:literal) => { $n };\n}\nmacro_rules! broken {\n\t($x) => { $x };\n}\n"),
+			("src/lib.rs", "#[macro_use]\nmod macros;\nroute!(health => handlers::health_check);\npub fn run() {\n\thealth();\n\tcall_all!(first, second,);\n\tlet x = double!(compute());\n\trecurse!();\n\tonly_numbers!(not_a_literal);\n\tbroken!(1);\n\tbroken!(2);\n\tsqlx::query!(\"SELECT 1\");\n}\n"),
+		]);
+
+		let health = &analysis.get_function("app::health").unwrap().graph;
+		assert!(health.nodes.iter().any(|node| node.key == "handlers::health_check"));
+		assert!(health.nodes.iter().any(|node| node.key == "crate::register"));
+
+		let run = &analysis.get_function("app::run").unwrap().graph;
+		for key in ["first", "second", "compute", "sqlx::query"] {
+			assert!(run.nodes.iter().any(|node| node.key == key), "missing node {}", key);
+		}
+		assert!(!run.nodes.iter().any(|node| ["call_all", "double"].contains(&node.key.as_str())));
+
+		// The items of expansions aren't in the index, so the calls to them aren't resolved
+		assert_eq!(run.nodes.iter().find(|node| node.key == "health").unwrap().path, None);
+		assert!(analysis.callers("app::health").is_empty());
+
+		let failed: Vec<&str> = analysis.diagnostics.iter().filter_map(|diagnostic| match diagnostic {
+			Error::MacroExpansion { name, .. } => Some(name.as_str()),
+			_ => None,
+		}).collect();
+		assert_eq!(failed, vec!["recurse", "only_numbers"]);
+
+		// Rules that can't be parsed are reported once at the definition
+		let malformed: Vec<(&str, &str, usize)> = analysis.diagnostics.iter().filter_map(|diagnostic| match diagnostic {
+			Error::MacroDefinition { path, name, span, .. } => Some((path.as_str(), name.as_str(), span.line)),
+			_ => None,
+		}).collect();
+		assert_eq!(malformed, vec![("src/macros.rs", "broken", 19)]);
+	}
+
+	/// Test that the methods of impl blocks are visited as functions keyed by their type and trait.
+	#[test]
+	fn impl_methods_are_functions() {
+		let files = [
+			("src/lib.rs", "pub mod users;\n"),
+			("src/users.rs", "pub struct UserService;\nimpl UserService {\n\t/// Create the service\n\tpub fn new() -> Self { UserService }\n\t/// Find a user\n\t#[doc(hidden)]\n\tpub fn find(&self, id: u32) -> User { load(id) }\n\t#[cfg(feature = \"admin\")]\n\tpub fn purge(&mut self) {}\n}\nimpl UserRepo for UserService {\n\tfn save(self: Box<Self>, user: User) { store(user); }\n}\nimpl<T> From<T> for crate::models::Wrapper<T> {\n\tfn from(value: T) -> Self { wrap(value) }\n}\n"),
+		];
+		let analysis = analyze_files(&files);
+
+		let keys: Vec<&str> = analysis.functions.iter().map(|function| function.key.as_str()).collect();
+		assert_eq!(keys, vec!["crate::users::UserService::new", "crate::users::UserService::find", "<crate::users::UserService as UserRepo>::save", "<crate::models::Wrapper as From<T>>::from"]);
+
+		let find = analysis.get_function("app::users::UserService::find").unwrap();
+		assert_eq!(find.receiver.as_deref(), Some("&self"));
+		assert_eq!(find.self_type.as_deref(), Some("crate::users::UserService"));
+		assert_eq!(find.trait_name, None);
+		assert!(find.graph.nodes.iter().any(|node| node.key == "load"));
+		assert_eq!(find.graph.nodes[0].doc, vec![" Find a user"]);
+		assert_eq!(analysis.get_function("app::users::UserService::new").unwrap().graph.nodes[0].doc, vec![" Create the service"]);
+		assert_eq!(analysis.get_function("app::users::UserService::new").unwrap().receiver, None);
+
+		let save = analysis.get_function("<app::users::UserService as UserRepo>::save").unwrap();
+		assert_eq!(save.receiver.as_deref(), Some("self: Box<Self>"));
+		assert_eq!(save.trait_name.as_deref(), Some("UserRepo"));
+		assert_eq!(save.path(), "<app::users::UserService as UserRepo>::save");
+
+		let files: BTreeMap<String, String> = files.iter().map(|(path, content)| (path.to_string(), content.to_string())).collect();
+		let options = Options { function_name: vec!["find".to_string()], ..Options::default() };
+		let filtered = ara::analyze_memory(options, MANIFEST, files).unwrap();
+		assert_eq!(filtered.functions.len(), 1);
+		assert_eq!(filtered.functions[0].key, "crate::users::UserService::find");
+	}
+
+	/// Test that method calls on traits are resolved to the trait and every impl of it with the way they're dispatched.
+	#[test]
+	fn trait_methods_are_resolved() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod repo;\npub mod service;\n"),
+			("src/repo.rs", "pub trait UserRepo {\n\tfn find_user(&self, id: u32) -> User;\n\t#[doc(hidden)]\n\tfn exists(&self, id: u32) -> bool { self.find_user(id).is_active() }\n}\npub struct PgRepo;\nimpl UserRepo for PgRepo {\n\tfn find_user(&self, id: u32) -> User { query(id) }\n}\npub struct MemoryRepo;\nimpl UserRepo for MemoryRepo {\n\tfn find_user(&self, id: u32) -> User { lookup(id) }\n\tfn exists(&self, id: u32) -> bool { true }\n}\n"),
+			("src/service.rs", "use std::sync::Arc;\nuse crate::repo::*;\npub fn dynamic(repo: Arc<dyn UserRepo>) { repo.find_user(1); }\npub fn generic<T>(repo: &T) where T: UserRepo { repo.exists(1); }\npub fn concrete(repo: PgRepo) { repo.exists(1); repo.find_user(1); repo.unknown(); }\n"),
+		]);
+
+		let default = analysis.get_function("app::repo::UserRepo::exists").unwrap();
+		assert_eq!(default.trait_name.as_deref(), Some("UserRepo"));
+		assert_eq!(default.self_type, None);
+		let call = default.graph.nodes.iter().find(|node| node.key == "find_user").unwrap();
+		assert_eq!(call.dispatch, Some(Dispatch::Generic));
+
+		let method = |function: &str, method: &str| {
+			let function = analysis.get_function(function).unwrap();
+			function.graph.nodes.iter().find(|node| node.key == method).cloned().unwrap()
+		};
+
+		let call = method("app::service::dynamic", "find_user");
+		assert_eq!(call.dispatch, Some(Dispatch::Dynamic));
+		assert_eq!(call.candidates, vec!["app::repo::UserRepo::find_user", "<app::repo::PgRepo as UserRepo>::find_user", "<app::repo::MemoryRepo as UserRepo>::find_user"]);
+
+		let call = method("app::service::generic", "exists");
+		assert_eq!(call.dispatch, Some(Dispatch::Generic));
+		assert_eq!(call.candidates, vec!["app::repo::UserRepo::exists", "<app::repo::MemoryRepo as UserRepo>::exists"]);
+
+		// PgRepo uses the default body of the trait
+		let call = method("app::service::concrete", "exists");
+		assert_eq!(call.dispatch, Some(Dispatch::Static));
+		assert_eq!(call.candidates, vec!["app::repo::UserRepo::exists"]);
+		assert_eq!(method("app::service::concrete", "find_user").candidates, vec!["<app::repo::PgRepo as UserRepo>::find_user"]);
+		assert_eq!(method("app::service::concrete", "unknown").dispatch, None);
+	}
+
+	/// Test that types with the same name in different modules only get the methods of their own impl blocks.
+	#[test]
+	fn same_named_types_are_kept_apart() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod a;\npub mod b;\npub fn run() {\n\tlet c = b::Config::new();\n\tc.load();\n\tlet d = a::Config::default();\n\td.load();\n}\n"),
+			("src/a.rs", "pub struct Config;\nimpl Config {\n\tpub fn default() -> Self { Config }\n\tpub fn load(&self) {}\n}\n"),
+			("src/b.rs", "pub struct Config;\nimpl Config {\n\tpub fn new() -> Self { Config }\n}\n"),
+		]);
+
+		let run = analysis.get_function("app::run").unwrap();
+		let loads: Vec<Vec<String>> = run.graph.nodes.iter().filter(|node| node.key == "load").map(|node| node.candidates.clone()).collect();
+		assert_eq!(loads, vec![Vec::new(), vec!["app::a::Config::load".to_string()]]);
+		assert_eq!(analysis.callers("app::a::Config::load").len(), 1);
+	}
+
+	/// Test that traits with the same name in different modules only get the impl blocks of their own trait.
+	#[test]
+	fn same_named_traits_are_kept_apart() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod a;\npub mod b;\npub mod service;\n"),
+			("src/a.rs", "pub trait Repo { fn find(&self); }\npub struct Pg;\nimpl Repo for Pg { fn find(&self) {} }\n"),
+			("src/b.rs", "pub trait Repo<T> { fn find(&self); fn count(&self) {} }\npub struct Memory;\nimpl Repo<u32> for Memory { fn find(&self) {} }\n"),
+			("src/service.rs", "use crate::a::Repo;\npub fn dynamic(repo: &dyn Repo) { repo.find(); }\npub fn generic<T: crate::b::Repo<u32>>(repo: T) { repo.find(); }\npub fn concrete(pg: crate::a::Pg, memory: crate::b::Memory) {\n\tpg.find();\n\tmemory.count();\n}\n"),
+		]);
+
+		let candidates = |function: &str, method: &str| -> Vec<String> {
+			let function = analysis.get_function(function).unwrap();
+			function.graph.nodes.iter().find(|node| node.key == method).unwrap().candidates.clone()
+		};
+		assert_eq!(candidates("app::service::dynamic", "find"), vec!["app::a::Repo::find", "<app::a::Pg as Repo>::find"]);
+		assert_eq!(candidates("app::service::generic", "find"), vec!["app::b::Repo::find", "<app::b::Memory as Repo<u32>>::find"]);
+		assert_eq!(candidates("app::service::concrete", "find"), vec!["<app::a::Pg as Repo>::find"]);
+		assert_eq!(candidates("app::service::concrete", "count"), vec!["app::b::Repo::count"]);
+	}
+
+	/// Test that the data types are registered by their fully qualified path and that aliases are resolved.
+	/// Doc attributes that aren't comments Eg. `#[doc(hidden)]` are skipped.
+	#[test]
+	fn types_are_registered() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod models;\npub mod error;\n"),
+			("src/models.rs", "/// A user of the app\n#[doc(hidden)]\n#[derive(Debug, Serialize)]\npub struct User<'a, T: Clone> {\n\t#[serde(rename = \"user name\")]\n\tpub name: &'a str,\n\tpub(crate) roles: Vec<T>,\n\tsecret: Option<Box<dyn Secret>>,\n}\npub struct Id(pub u64);\nimpl Id {\n\t#[doc(hidden)]\n\tpub fn raw(&self) -> u64 { self.0 }\n}\npub enum Status {\n\t/// Can log in\n\tActive = 1,\n\t#[doc(hidden)]\n\tBanned { reason: String },\n}\nmod inner {\n\tpub union Bits { int: u32, float: f32 }\n}\n"),
+			("src/error.rs", "pub type Result<T> = std::result::Result<T, AppError>;\npub type Users = Result<Vec<User>>;\n"),
+		]);
+
+		let user = analysis.types.get("app::models::User").unwrap();
+		assert_eq!(user.kind, TypeKind::Struct);
+		assert_eq!(user.generics, vec!["'a", "T: Clone"]);
+		assert_eq!(user.visibility, "pub");
+		assert_eq!(user.doc, vec![" A user of the app"]);
+		assert_eq!(user.attrs, vec!["derive(Debug, Serialize)"]);
+		let fields: Vec<(&str, &str, &str)> = user.fields.iter().map(|field| (field.name.as_str(), field.ty.as_str(), field.visibility.as_str())).collect();
+		assert_eq!(fields, vec![("name", "&'a str", "pub"), ("roles", "Vec<T>", "pub(crate)"), ("secret", "Option<Box<dyn Secret>>", "")]);
+		assert_eq!(user.fields[0].attrs, vec!["serde(rename=\"user name\")"]);
+
+		assert_eq!(analysis.types.get("app::models::Id").unwrap().fields[0].name, "0");
+		let status = analysis.types.get("app::models::Status").unwrap();
+		assert_eq!(status.variants[0].discriminant.as_deref(), Some("1"));
+		assert_eq!(status.variants[0].doc, vec![" Can log in"]);
+		assert_eq!(status.variants[1].fields[0].ty, "String");
+		assert!(status.variants[1].doc.is_empty());
+		assert!(analysis.get_function("app::models::Id::raw").unwrap().graph.nodes[0].doc.is_empty());
+		assert_eq!(analysis.types.get("app::models::inner::Bits").unwrap().kind, TypeKind::Union);
+
+		assert_eq!(analysis.types.resolve("app::error::Result<User>").as_deref(), Some("std::result::Result<User, AppError>"));
+		assert_eq!(analysis.types.resolve("app::error::Users").as_deref(), Some("std::result::Result<Vec<User>, AppError>"));
+		assert_eq!(analysis.types.resolve("app::models::User").as_deref(), Some("app::models::User"));
+	}
+
+	/// Test that the data types in the bodies of functions are registered under the path of the function.
+	#[test]
+	fn block_types_are_registered() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod routes;\n"),
+			("src/routes.rs", "pub fn index() {\n\tstruct Payload { id: u32 }\n\tif ready() {\n\t\tenum State { On, Off }\n\t}\n\tfn helper() {\n\t\ttype Id = u32;\n\t}\n\t#[cfg(feature = \"missing\")]\n\tstruct Hidden;\n}\npub struct Service;\nimpl Service {\n\tpub fn run(&self) { let _ = || { union Bits { int: u32 } }; }\n}\npub trait Repo {\n\tfn find(&self) { struct Query; }\n}\nconst LIMIT: u32 = { struct Limit; 8 };\n"),
+		]);
+
+		let paths: Vec<&str> = analysis.types.types.keys().map(String::as_str).collect();
+		assert_eq!(paths, vec![
+			"app::routes::LIMIT::Limit",
+			"app::routes::Repo::find::Query",
+			"app::routes::Service",
+			"app::routes::Service::run::Bits",
+			"app::routes::index::Payload",
+			"app::routes::index::State",
+			"app::routes::index::helper::Id",
+		]);
+		assert_eq!(analysis.types.get("app::routes::index::Payload").unwrap().fields[0].name, "id");
+	}
+
+	/// Test that closures, async blocks and the bodies of loops are visited and that closures get a node of their own.
+	#[test]
+	fn closures_and_loop_bodies_are_visited() {
+		let analysis = analyze_files(&[("src/lib.rs", "pub async fn handler(ids: Vec<u32>) {\n\tlet users = ids.iter().map(|id| service.load(id)).collect();\n\ttokio::spawn(async move { notify(users).await; });\n\tfor id in ids { audit(id); }\n\twhile running() { tick(); }\n\tloop { wait(); }\n\tunsafe { raw(); }\n\tlet _ = try { attempt()? };\n\tlet handle = move |a, b: u32| combine(a, b);\n}\n")]);
+		let graph = &analysis.get_function("app::handler").unwrap().graph;
+		let node = |key: &str| graph.nodes.iter().find(|node| node.key == key).unwrap_or_else(|| panic!("missing node {}", key));
+
+		for key in ["notify", "audit", "tick", "wait", "raw", "attempt", "combine"] {
+			assert_eq!(node(key).node_type, NodeType::Call);
+		}
+		assert_eq!(node("|id|").node_type, NodeType::Closure);
+		assert_eq!(node("move |a, b: u32|").node_type, NodeType::Closure);
+		assert!(graph.edges.contains(&Edge::new(node("map").id, node("|id|").id)));
+		assert!(graph.edges.contains(&Edge::new(node("|id|").id, node("service").id)));
+		assert!(graph.edges.contains(&Edge::new(node("tokio::spawn").id, node("notify").id)));
+		assert!(graph.edges.contains(&Edge::new(node("move |a, b: u32|").id, node("combine").id)));
+	}
+
+	/// Test that every kind of expression is visited and that the constructs that aren't interpreted are reported.
+	#[test]
+	fn unsupported_constructs_are_reported() {
+		let analysis = analyze_files(&[("src/lib.rs", "pub fn handler(items: &mut [u32]) -> u32 {\n\titems[index()] = -negate() as u32;\n\tlet total = user().name.len() + [first(), second()][0];\n\tlet range = start()..end();\n\tlet zeros = [zero(); count()];\n\tif let Some(x) = find() && valid(x) { return inner(); }\n\tlet Some(y) = lookup() else { fallback(); return 0 };\n\tlet value = loop { break compute(); };\n\tmatch status() { Status::Ok if check() => ok(), _ => other() }\n\tlet config = Config { name: name(), ..defaults() };\n\tsqlx::query!(\"SELECT 1\", hidden());\n\tsqlx::query!(\"SELECT 2\");\n\tmod nested {}\n\tvalue\n}\n")]);
+		let graph = &analysis.get_function("app::handler").unwrap().graph;
+
+		for key in ["index", "negate", "user", "first", "second", "start", "end", "zero", "count", "find", "valid", "inner", "lookup", "fallback", "compute", "status", "check", "ok", "other", "name", "defaults"] {
+			assert!(graph.nodes.iter().any(|node| node.key == key), "missing node {}", key);
+		}
+
+		let unsupported: Vec<(&str, usize)> = analysis.unsupported.iter().map(|unsupported| (unsupported.construct.as_str(), unsupported.line)).collect();
+		assert_eq!(unsupported, vec![("macro `sqlx::query!`", 11), ("macro `sqlx::query!`", 12), ("item in a block", 13)]);
+		assert_eq!(analysis.unsupported_summary().unwrap(), "3 unsupported constructs were not interpreted:\n  item in a block x1 (src/lib.rs:13)\n  macro `sqlx::query!` x2 (src/lib.rs:11, src/lib.rs:12)");
+		assert_eq!(analyze_files(&[("src/lib.rs", "pub fn empty() {}\n")]).unsupported_summary(), None);
+	}
+
+	/// Test that the initializers of constants and statics get graphs of their own and that the other items outside of
+	/// functions aren't visited for calls.
+	#[test]
+	fn items_outside_of_functions() {
+		let analysis = analyze_files(&[("src/lib.rs", "pub const MAX: u32 = limit(2);\nstatic NAME: &str = name();\ntype Buffer = [u8; size()];\npub union Bits { int: u32, float: f32 }\nextern \"C\" { fn abs(input: i32) -> i32; }\npub fn limit(factor: u32) -> u32 {\n\tconst BASE: u32 = base();\n\tBASE * factor\n}\npub const fn base() -> u32 { 8 }\n")]);
+
+		let max = analysis.get_function("app::MAX").unwrap();
+		assert_eq!(max.graph.nodes[0].node_type, NodeType::Function);
+		assert!(max.graph.nodes.iter().any(|node| node.key == "limit" && node.path.as_deref() == Some("app::limit")));
+		assert_eq!(max.graph.edges, vec![Edge::new(0, 1)]);
+		assert!(analysis.get_function("app::NAME").unwrap().graph.nodes.iter().any(|node| node.key == "name"));
+		assert_eq!(analysis.callers("app::limit"), vec!["app::MAX"]);
+
+		// Constants in a function are under the function, and the function goes on after them
+		let base = analysis.get_function("app::limit::BASE").unwrap();
+		assert!(base.graph.nodes.iter().any(|node| node.key == "base"));
+		let limit = analysis.get_function("app::limit").unwrap();
+		assert!(!limit.graph.nodes.iter().any(|node| node.key == "base"));
+		assert!(limit.graph.nodes.iter().any(|node| node.key == "factor" && node.node_type == NodeType::Local));
+
+		let mut paths: Vec<String> = analysis.functions.iter().map(|function| function.path()).collect();
+		paths.sort();
+		assert_eq!(paths, vec!["app::MAX", "app::NAME", "app::base", "app::limit", "app::limit::BASE"]);
+		assert!(analysis.unsupported.is_empty());
+	}
+
+	/// Test that glob imports of modules that import each other don't look the same names up over and over again.
+	#[test]
+	fn glob_cycles_are_resolved_once() {
+		// Every module is in the directory of its parent Eg. "src/m1/m2.rs"
+		let path = |i: usize| format!("src/{}.rs", (1..=i).map(|j| format!("m{}", j)).collect::<Vec<_>>().join("/"));
+		let mut files = vec![("src/lib.rs".to_string(), "pub mod m1;\npub use m1::*;\n".to_string())];
+		for i in 1..6 {
+			files.push((path(i), format!("use super::*;\npub mod m{};\npub use m{}::*;\n", i + 1, i + 1)));
+		}
+		files.push((path(6), "use super::*;\npub fn run() { tokio::spawn(); }\n".to_string()));
+		let analysis = ara::analyze_memory(Options::default(), MANIFEST, files.into_iter().collect()).unwrap();
+
+		let run = analysis.get_function("app::m1::m2::m3::m4::m5::m6::run").unwrap();
+		let call = run.graph.nodes.iter().find(|node| node.key == "tokio::spawn").unwrap();
+		assert_eq!(call.path.as_deref(), Some("tokio::spawn"));
+		assert_eq!(call.external.as_deref(), Some("tokio"));
+	}
+
+	/// Test that calls are resolved through the imports of the module to the canonical path of the item.
+	#[test]
+	fn imports_are_resolved() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod routes;\npub mod db;\nmod prelude {\n\tpub use crate::db::connect as open;\n}\nextern crate serde_json as json;\npub fn start() { routes::health(); crate::routes::index(); }\n"),
+			("src/routes.rs", "use crate::db::{self, query as run};\nuse super::prelude::*;\nuse std::sync::Arc;\npub use handlers::health;\nmod handlers {\n\tpub fn health() { super::super::db::insert(); }\n}\npub fn index() {\n\tlet value = run();\n\tdb::insert(value);\n\topen();\n\tArc::new(1);\n\thealth();\n\tjson::to_string();\n\tOk::<(), ()>(());\n\tunknown();\n}\n"),
+			("src/db.rs", "pub fn query() {}\npub fn insert() {}\npub fn connect() {}\npub struct Pool;\nimpl Pool {\n\tpub fn new() -> Self { Self::build() }\n}\n"),
+		]);
+
+		let paths = |function: &str| -> Vec<(String, Option<String>)> {
+			let graph = &analysis.get_function(function).unwrap().graph;
+			graph.nodes.iter().filter(|node| node.node_type == NodeType::Call).map(|node| (node.key.clone(), node.path.clone())).collect()
+		};
+		let path = |key: &str, path: &str| (key.to_string(), Some(path.to_string()));
+
+		assert_eq!(paths("app::routes::index"), vec![
+			path("run", "app::db::query"),
+			path("db::insert", "app::db::insert"),
+			path("open", "app::db::connect"),
+			path("Arc::new", "std::sync::Arc::new"),
+			path("health", "app::routes::handlers::health"),
+			path("json::to_string", "serde_json::to_string"),
+			path("Ok", "std::result::Result::Ok"),
+			("unknown".to_string(), None),
+		]);
+		assert_eq!(paths("app::start"), vec![path("routes::health", "app::routes::handlers::health"), path("crate::routes::index", "app::routes::index")]);
+		assert_eq!(paths("app::routes::handlers::health"), vec![path("super::super::db::insert", "app::db::insert")]);
+		assert_eq!(paths("app::db::Pool::new"), vec![path("Self::build", "app::db::Pool::build")]);
+	}
+
+	/// Test that imports without `pub` are only followed from the module they're in and the modules inside of it.
+	#[test]
+	fn private_imports_stay_in_their_module() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod db;\npub mod routes;\npub mod admin;\n"),
+			("src/db.rs", "pub fn query() {}\n"),
+			("src/routes.rs", "use crate::db::query;\npub use crate::db::query as run;\npub fn index() { query(); }\nmod nested {\n\tpub fn inner() { super::query(); }\n}\n"),
+			("src/admin.rs", "use crate::routes::*;\npub fn panel() {\n\tcrate::routes::query();\n\trun();\n\tquery();\n}\n"),
+		]);
+
+		let paths = |function: &str| -> Vec<Option<String>> {
+			let graph = &analysis.get_function(function).unwrap().graph;
+			graph.nodes.iter().filter(|node| node.node_type == NodeType::Call).map(|node| node.path.clone()).collect()
+		};
+		let path = |path: &str| Some(path.to_string());
+
+		assert_eq!(paths("app::routes::index"), vec![path("app::db::query")]);
+		assert_eq!(paths("app::routes::nested::inner"), vec![path("app::db::query")]);
+		assert_eq!(paths("app::admin::panel"), vec![path("app::routes::query"), path("app::db::query"), None]);
+		// `run` is re-exported with `pub use`
+		assert_eq!(analysis.callers("app::db::query"), vec!["app::routes::index", "app::routes::nested::inner", "app::admin::panel"]);
+	}
+
+	/// Test that imports and items in a block can only be used in the block and nested functions resolve to themselves.
+	#[test]
+	fn block_items_are_scoped() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod routes;\npub mod db;\n"),
+			("src/routes.rs", "pub fn helper() {}\npub fn index() {\n\tuse crate::db::query as run;\n\tfn helper() { run(); }\n\thelper();\n\t{\n\t\tuse crate::db::insert as run;\n\t\trun();\n\t}\n\trun();\n}\npub fn other() {\n\trun();\n\thelper();\n}\n"),
+			("src/db.rs", "pub fn query() {}\npub fn insert() {}\n"),
+		]);
+
+		let paths = |function: &str| -> Vec<(String, Option<String>)> {
+			let graph = &analysis.get_function(function).unwrap().graph;
+			graph.nodes.iter().filter(|node| node.node_type == NodeType::Call).map(|node| (node.key.clone(), node.path.clone())).collect()
+		};
+		let path = |key: &str, path: &str| (key.to_string(), Some(path.to_string()));
+
+		assert_eq!(paths("app::routes::index"), vec![path("helper", "app::routes::index::helper"), path("run", "app::db::insert"), path("run", "app::db::query")]);
+		assert_eq!(paths("app::routes::index::helper"), vec![path("run", "app::db::query")]);
+		assert_eq!(paths("app::routes::other"), vec![("run".to_string(), None), path("helper", "app::routes::helper")]);
+	}
+
+	/// Test that locals are only local in their scope and every name of a pattern is bound.
+	#[test]
+	fn locals_are_scoped() {
+		let analysis = analyze_files(&[("src/lib.rs", "pub fn first(id: &str) {\n\tlet id = id.parse();\n\tlet (user, Config { name, .. }, [head, rest @ ..]) = load();\n\tsave(user, name, head, rest);\n\tif let Some(found) = find() { found(); }\n\tfound();\n\tmatch status() { Ok(code) => code(), Err(_) => None }\n\titems.map(|item| item());\n\tfor entry in entries() { entry(); }\n\t{ let inner = 1; }\n\tinner();\n}\npub fn second() {\n\tuser();\n\tid();\n}\n")]);
+		let types = |function: &str| -> Vec<(String, NodeType)> {
+			let graph = &analysis.get_function(function).unwrap().graph;
+			graph.nodes.iter().filter(|node| matches!(node.node_type, NodeType::Call | NodeType::Local)).map(|node| (node.key.clone(), node.node_type.clone())).collect()
+		};
+		let local = |key: &str| (key.to_string(), NodeType::Local);
+		let call = |key: &str| (key.to_string(), NodeType::Call);
+
+		assert_eq!(types("app::first"), vec![
+			local("id"),
+			call("load"),
+			call("save"), local("user"), local("name"), local("head"), local("rest"),
+			call("find"), local("found"),
+			call("found"),
+			call("status"), local("code"), call("None"),
+			call("items"), local("item"),
+			call("entries"), local("entry"),
+			call("inner"),
+		]);
+		let graph = &analysis.get_function("app::first").unwrap().graph;
+		assert_eq!(graph.nodes.iter().find(|node| node.key == "load").unwrap().local, "user, name, head, rest");
+		assert_eq!(graph.nodes.iter().find(|node| node.key == "parse").unwrap().local, "id");
+		assert_eq!(graph.nodes.iter().find(|node| node.key == "id").unwrap().local, "");
+		assert_eq!(types("app::second"), vec![call("user"), call("id")]);
+	}
+
+	/// Test that the types of locals are inferred and method calls on them are resolved to the method of the type.
+	#[test]
+	fn method_calls_are_resolved_by_type() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod models;\npub mod db;\nuse db::Pool;\npub struct AppState {\n\tpub pool: Pool,\n}\npub struct Admin;\nimpl Admin {\n\tpub fn save(&self) {}\n}\npub fn handler(pool: Pool) -> Result<(), Error> {\n\tlet user = models::User::new();\n\tuser.save();\n\tlet admin: Admin = load();\n\tadmin.save();\n\tlet found = db::find(&pool).await?;\n\tfound.save();\n\tlet state = AppState { pool };\n\tstate.pool.query();\n\tother.save();\n\tmissing();\n\tOk(())\n}\n"),
+			("src/models.rs", "pub struct User;\nimpl User {\n\tpub fn new() -> Self { User }\n\tpub fn save(&self) {}\n}\n"),
+			("src/db.rs", "pub struct Pool;\nimpl Pool {\n\tpub fn query(&self) {}\n}\npub struct Record;\nimpl Record {\n\tpub fn save(&self) {}\n}\npub async fn find(pool: &Pool) -> Result<Record, Error> { Ok(Record) }\n"),
+		]);
+		let graph = &analysis.get_function("app::handler").unwrap().graph;
+
+		let methods: Vec<(&str, Option<&str>, bool)> = graph.nodes.iter()
+			.filter(|node| node.node_type == NodeType::Method)
+			.map(|node| (node.key.as_str(), node.path.as_deref(), node.resolved))
+			.collect();
+		assert_eq!(methods, vec![
+			("save", Some("app::models::User::save"), true),
+			("save", Some("app::Admin::save"), true),
+			("save", Some("app::db::Record::save"), true),
+			("query", Some("app::db::Pool::query"), true),
+			("save", None, false),
+		]);
+
+		let missing = graph.nodes.iter().find(|node| node.key == "missing").unwrap();
+		assert!(!missing.resolved);
+		assert!(format!("{:?}", missing).ends_with(" Unresolved"));
+		assert!(graph.nodes.iter().find(|node| node.key == "Ok").unwrap().resolved);
+	}
+
+	/// Test that the functions that are called are resolved through the imports to get the type they return, even when
+	/// other functions have the same name.
+	#[test]
+	fn return_types_follow_the_imports() {
+		let analysis = analyze_files(&[
+			("src/lib.rs", "pub mod models;\npub mod db;\nuse db::load;\npub fn run() {\n\tload().save();\n\tmodels::load().save();\n\tother_crate::load().save();\n}\n"),
+			("src/models.rs", "pub struct User;\nimpl User {\n\tpub fn save(&self) {}\n}\npub fn load() -> User { User }\n"),
+			("src/db.rs", "pub struct Record;\nimpl Record {\n\tpub fn save(&self) {}\n}\npub fn load() -> Record { Record }\n"),
+		]);
+
+		let graph = &analysis.get_function("app::run").unwrap().graph;
+		let saves: Vec<Option<&str>> = graph.nodes.iter().filter(|node| node.key == "save").map(|node| node.path.as_deref()).collect();
+		assert_eq!(saves, vec![Some("app::db::Record::save"), Some("app::models::User::save"), None]);
+	}
+
+	/// Test that calls are linked to the functions they call across files and crates, and external calls are marked.
+	#[test]
+	fn calls_are_linked_across_crates() {
+		let mut source = MemorySource::new("[workspace]\nmembers = [\"api\", \"store\"]\n");
+		source.add_file("api/Cargo.toml", "[package]\nname = \"api\"\n\n[dependencies]\nstore = { path = \"../store\" }\n")
+			.add_file("api/src/lib.rs", "mod routes;\npub struct Repo;\nimpl Repo {\n\tpub fn find(&self) {}\n}\npub fn main() {\n\troutes::create_routes();\n\tstore::save();\n\tserde_json::to_string();\n\tlet repo = Repo::new();\n\trepo.find();\n\tVec::new();\n}\n")
+			.add_file("api/src/routes.rs", "pub fn create_routes() { helper(); }\nfn helper() {}\n")
+			.add_file("store/Cargo.toml", "[package]\nname = \"store\"\n")
+			.add_file("store/src/lib.rs", "pub struct Repo;\nimpl Repo {\n\tpub fn find(&self) {}\n}\npub fn save() {}\n");
+
+		let analysis = ara::analyze_source(Options::default(), &source).unwrap();
+		assert_eq!(analysis.callees("api::main"), vec!["api::routes::create_routes", "store::save", "api::Repo::find"]);
+		assert_eq!(analysis.callers("api::routes::helper"), vec!["api::routes::create_routes"]);
+
+		let graph = &analysis.functions.iter().find(|function| function.path() == "api::main").unwrap().graph;
+		let externals: Vec<(&str, Option<&str>)> = graph.nodes.iter().filter(|node| node.node_type == NodeType::Call).map(|node| (node.key.as_str(), node.external.as_deref())).collect();
+		assert_eq!(externals, vec![("routes::create_routes", None), ("store::save", None), ("serde_json::to_string", Some("serde_json")), ("Repo::new", None), ("Vec::new", Some("std"))]);
+		let link = analysis.links.iter().find(|link| link.callee == "store::save").unwrap();
+		assert_eq!(graph.get_node(link.node).unwrap().key, "store::save");
 	}
-}
\ No newline at end of file
+}